@@ -0,0 +1,42 @@
+mod daylight;
+mod environment;
+
+pub use daylight::Daylight;
+pub use environment::Environment;
+
+use super::{texture, Color, Point, Sampler, Texture, Vec3};
+
+/// Radiance arriving from infinitely far away, seen by rays that escape the scene.
+pub trait Background: Send + Sync {
+    fn radiance(&self, direction: Vec3) -> Color;
+
+    /// Picks a direction towards the background, returning the direction,
+    /// the radiance arriving from it and its pdf with respect to solid angle.
+    ///
+    /// Backgrounds that cannot be importance sampled return `None`.
+    fn sample(&self, _sampler: &mut dyn Sampler) -> Option<(Vec3, Color, f32)> {
+        None
+    }
+
+    /// The solid angle density with which `sample` picks `direction`.
+    fn pdf(&self, _direction: Vec3) -> f32 {
+        0.0
+    }
+}
+
+impl<T: Texture> Background for T {
+    fn radiance(&self, direction: Vec3) -> Color {
+        let (u, v) = texture::uv_coords(direction);
+        let p = Point::new(f32::INFINITY, f32::INFINITY, f32::INFINITY);
+        self.color(u, v, p)
+    }
+}
+
+/// Inverse of `texture::uv_coords`.
+pub(crate) fn uv_direction(u: f32, v: f32) -> Vec3 {
+    let theta = v * std::f32::consts::PI;
+    let phi = u * std::f32::consts::TAU - std::f32::consts::PI;
+
+    let (sin_theta, cos_theta) = theta.sin_cos();
+    Vec3::new(sin_theta * phi.cos(), -cos_theta, -sin_theta * phi.sin())
+}
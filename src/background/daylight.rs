@@ -0,0 +1,261 @@
+use super::Background;
+use crate::{Color, Sampler, Vec3};
+use std::f32::consts::{FRAC_PI_2, PI};
+
+const SUN_ANGULAR_RADIUS: f32 = 0.004_65;
+const SUN_LUMINANCE: f32 = 1.6e6;
+const SUN_SAMPLE_PROBABILITY: f32 = 0.5;
+
+/// An analytic daylight sky following the Preetham model, with an optional sun disk.
+///
+/// Radiance is expressed in kcd/m² scaled by `intensity`. Directions below the
+/// horizon see a diffuse ground lit by the sky and sun.
+pub struct Daylight {
+    sun_direction: Vec3,
+    sun_radiance: Option<Color>,
+    intensity: f32,
+    zenith: (f32, f32, f32),
+    perez: [[f32; 5]; 3],
+    ground: Color,
+}
+
+impl Daylight {
+    /// `elevation` and `azimuth` give the sun position in degrees. Azimuth is
+    /// measured from the +X axis towards -Z. Typical turbidity values lie
+    /// between 2 (clear) and 10 (hazy).
+    pub fn new(
+        elevation: f32,
+        azimuth: f32,
+        turbidity: f32,
+        ground_albedo: Color,
+        intensity: f32,
+        sun: bool,
+    ) -> Self {
+        let (elevation, azimuth) = (elevation.to_radians(), azimuth.to_radians());
+        let sun_direction = Vec3::new(
+            elevation.cos() * azimuth.cos(),
+            elevation.sin(),
+            -elevation.cos() * azimuth.sin(),
+        );
+
+        let t = turbidity;
+        let theta_s = FRAC_PI_2 - elevation.max(0.0);
+
+        let chi = (4.0 / 9.0 - t / 120.0) * (PI - 2.0 * theta_s);
+        let zenith_y = ((4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192).max(0.0);
+        let zenith_x = chromaticity(t, theta_s, ZENITH_X);
+        let zenith_yc = chromaticity(t, theta_s, ZENITH_Y);
+
+        let perez = [
+            [
+                0.1787 * t - 1.4630,
+                -0.3554 * t + 0.4275,
+                -0.0227 * t + 5.3251,
+                0.1206 * t - 2.5771,
+                -0.0670 * t + 0.3703,
+            ],
+            [
+                -0.0193 * t - 0.2592,
+                -0.0665 * t + 0.0008,
+                -0.0004 * t + 0.2125,
+                -0.0641 * t - 0.8989,
+                -0.0033 * t + 0.0452,
+            ],
+            [
+                -0.0167 * t - 0.2608,
+                -0.0950 * t + 0.0092,
+                -0.0079 * t + 0.2102,
+                -0.0441 * t - 1.6537,
+                -0.0109 * t + 0.0529,
+            ],
+        ];
+
+        let sun_radiance = if sun && elevation > 0.0 {
+            Some(sun_transmittance(t, theta_s) * SUN_LUMINANCE)
+        } else {
+            None
+        };
+
+        let mut sky = Self {
+            sun_direction,
+            sun_radiance,
+            intensity,
+            zenith: (zenith_y, zenith_x, zenith_yc),
+            perez,
+            ground: Color::new(0.0, 0.0, 0.0),
+        };
+        sky.ground = ground_albedo * sky.horizontal_irradiance() / PI;
+        sky
+    }
+
+    fn sky(&self, direction: Vec3) -> Color {
+        let direction = direction.unit();
+
+        let cos_theta = direction.y.max(0.01);
+        let cos_gamma = direction.dot(&self.sun_direction).clamp(-1.0, 1.0);
+        let gamma = cos_gamma.acos();
+        let theta_s = self.sun_direction.y.clamp(0.0, 1.0).acos();
+
+        let (zenith_y, zenith_x, zenith_yc) = self.zenith;
+        let relative = |coefficients: &[f32; 5]| {
+            perez(coefficients, cos_theta, gamma, cos_gamma)
+                / perez(coefficients, 1.0, theta_s, theta_s.cos())
+        };
+
+        let luminance = zenith_y * relative(&self.perez[0]);
+        let x = zenith_x * relative(&self.perez[1]);
+        let y = zenith_yc * relative(&self.perez[2]);
+
+        xyy_to_rgb(x, y, luminance)
+    }
+
+    fn sun(&self, direction: Vec3) -> Option<Color> {
+        let cos_max = SUN_ANGULAR_RADIUS.cos();
+        match self.sun_radiance {
+            Some(radiance) if direction.unit().dot(&self.sun_direction) >= cos_max => {
+                Some(radiance)
+            }
+            _ => None,
+        }
+    }
+
+    /// Irradiance on an upward facing surface, integrated numerically over the sky.
+    fn horizontal_irradiance(&self) -> f32 {
+        let (steps_theta, steps_phi) = (32, 64);
+        let d_theta = FRAC_PI_2 / steps_theta as f32;
+        let d_phi = 2.0 * PI / steps_phi as f32;
+
+        let mut irradiance = 0.0;
+        for i in 0..steps_theta {
+            let theta = (i as f32 + 0.5) * d_theta;
+            for j in 0..steps_phi {
+                let phi = (j as f32 + 0.5) * d_phi;
+                let direction = Vec3::new(
+                    theta.sin() * phi.cos(),
+                    theta.cos(),
+                    theta.sin() * phi.sin(),
+                );
+                let radiance = self.sky(direction).luminance();
+                irradiance += radiance * theta.cos() * theta.sin() * d_theta * d_phi;
+            }
+        }
+
+        if let Some(radiance) = self.sun_radiance {
+            let solid_angle = 2.0 * PI * (1.0 - SUN_ANGULAR_RADIUS.cos());
+            irradiance += radiance.luminance() * solid_angle * self.sun_direction.y.max(0.0);
+        }
+
+        irradiance
+    }
+}
+
+impl Background for Daylight {
+    fn radiance(&self, direction: Vec3) -> Color {
+        if direction.y < 0.0 {
+            return self.ground * self.intensity;
+        }
+
+        let sky = self.sky(direction);
+        let sun = self.sun(direction).unwrap_or(Color::new(0.0, 0.0, 0.0));
+        (sky + sun) * self.intensity
+    }
+
+    fn sample(&self, sampler: &mut dyn Sampler) -> Option<(Vec3, Color, f32)> {
+        self.sun_radiance?;
+
+        let choice = sampler.next_1d();
+        let direction = if choice < SUN_SAMPLE_PROBABILITY {
+            let (u, v) = sampler.next_2d();
+            let cos_max = SUN_ANGULAR_RADIUS.cos();
+            let cos_theta = 1.0 - u * (1.0 - cos_max);
+            let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+            let phi = 2.0 * PI * v;
+
+            let (s, t) = self.sun_direction.orthonormal_basis();
+            s * (sin_theta * phi.cos())
+                + t * (sin_theta * phi.sin())
+                + self.sun_direction * cos_theta
+        } else {
+            Vec3::random_unit_vector(sampler)
+        };
+
+        Some((direction, self.radiance(direction), self.pdf(direction)))
+    }
+
+    fn pdf(&self, direction: Vec3) -> f32 {
+        if self.sun_radiance.is_none() {
+            return 0.0;
+        }
+
+        let uniform = (1.0 - SUN_SAMPLE_PROBABILITY) / (4.0 * PI);
+        let cos_max = SUN_ANGULAR_RADIUS.cos();
+        if direction.unit().dot(&self.sun_direction) >= cos_max {
+            uniform + SUN_SAMPLE_PROBABILITY / (2.0 * PI * (1.0 - cos_max))
+        } else {
+            uniform
+        }
+    }
+}
+
+const ZENITH_X: [[f32; 4]; 3] = [
+    [0.00166, -0.00375, 0.00209, 0.0],
+    [-0.02903, 0.06377, -0.03202, 0.00394],
+    [0.11693, -0.21196, 0.06052, 0.25886],
+];
+
+const ZENITH_Y: [[f32; 4]; 3] = [
+    [0.00275, -0.00610, 0.00317, 0.0],
+    [-0.04214, 0.08970, -0.04153, 0.00516],
+    [0.15346, -0.26756, 0.06670, 0.26688],
+];
+
+fn chromaticity(t: f32, theta_s: f32, m: [[f32; 4]; 3]) -> f32 {
+    let ts = [theta_s.powi(3), theta_s.powi(2), theta_s, 1.0];
+    let tt = [t * t, t, 1.0];
+
+    let mut sum = 0.0;
+    for (row, weight) in m.iter().zip(tt) {
+        for (c, s) in row.iter().zip(ts) {
+            sum += weight * c * s;
+        }
+    }
+    sum
+}
+
+fn perez([a, b, c, d, e]: &[f32; 5], cos_theta: f32, gamma: f32, cos_gamma: f32) -> f32 {
+    (1.0 + a * (b / cos_theta).exp()) * (1.0 + c * (d * gamma).exp() + e * cos_gamma * cos_gamma)
+}
+
+fn xyy_to_rgb(x: f32, y: f32, luminance: f32) -> Color {
+    if y <= 0.0 {
+        return Color::new(0.0, 0.0, 0.0);
+    }
+
+    let big_x = x / y * luminance;
+    let big_z = (1.0 - x - y) / y * luminance;
+
+    Color::new(
+        (3.2406 * big_x - 1.5372 * luminance - 0.4986 * big_z).max(0.0),
+        (-0.9689 * big_x + 1.8758 * luminance + 0.0415 * big_z).max(0.0),
+        (0.0557 * big_x - 0.2040 * luminance + 1.0570 * big_z).max(0.0),
+    )
+}
+
+/// Attenuation of sunlight through the atmosphere from Rayleigh and aerosol scattering,
+/// evaluated at representative wavelengths for the red, green and blue channels.
+fn sun_transmittance(turbidity: f32, theta_s: f32) -> Color {
+    let zenith_degrees = theta_s.to_degrees();
+    let air_mass = 1.0 / (theta_s.cos() + 0.15 * (93.885 - zenith_degrees).powf(-1.253));
+
+    let beta = 0.04608 * turbidity - 0.04586;
+    let channel = |wavelength: f32, rayleigh_depth: f32| {
+        let aerosol_depth = beta * wavelength.powf(-1.3);
+        (-air_mass * (rayleigh_depth + aerosol_depth)).exp()
+    };
+
+    Color::new(
+        channel(0.68, 0.0433),
+        channel(0.55, 0.0975),
+        channel(0.44, 0.2296),
+    )
+}
@@ -0,0 +1,87 @@
+use super::{uv_direction, Background};
+use crate::distribution::Distribution2D;
+use crate::{texture, Color, HdrImage, Sampler, Vec3};
+use std::f32::consts::PI;
+
+/// An equirectangular environment map, importance sampled by luminance.
+pub struct Environment {
+    image: HdrImage,
+    rotation: f32,
+    intensity: f32,
+    distribution: Distribution2D,
+}
+
+impl Environment {
+    /// `rotation` turns the map around the vertical axis, in degrees.
+    /// `intensity` scales the radiance of every pixel.
+    pub fn new(image: HdrImage, rotation: f32, intensity: f32) -> Self {
+        let (width, height) = (image.width(), image.height());
+
+        // Rows are stored top to bottom, while `v` runs from the bottom of the sphere.
+        let mut func = Vec::with_capacity(width * height);
+        for row in 0..height {
+            let y = height - 1 - row;
+            let sin_theta = ((row as f32 + 0.5) / height as f32 * PI).sin();
+            for x in 0..width {
+                func.push(image.get_pixel(x, y).luminance() * sin_theta);
+            }
+        }
+
+        let distribution = Distribution2D::new(&func, width, height);
+
+        Self {
+            image,
+            rotation,
+            intensity,
+            distribution,
+        }
+    }
+
+    fn lookup(&self, u: f32, v: f32) -> Color {
+        let (width, height) = (self.image.width(), self.image.height());
+        let x = ((u * width as f32) as usize).min(width - 1);
+        let y = (((1.0 - v) * height as f32) as usize).min(height - 1);
+        self.image.get_pixel(x, y) * self.intensity
+    }
+
+    fn to_local(&self, direction: Vec3) -> Vec3 {
+        direction.rotate(Vec3::new(0.0, 1.0, 0.0), -self.rotation.to_radians())
+    }
+
+    fn to_world(&self, direction: Vec3) -> Vec3 {
+        direction.rotate(Vec3::new(0.0, 1.0, 0.0), self.rotation.to_radians())
+    }
+}
+
+impl Background for Environment {
+    fn radiance(&self, direction: Vec3) -> Color {
+        let (u, v) = texture::uv_coords(self.to_local(direction));
+        self.lookup(u, v)
+    }
+
+    fn sample(&self, sampler: &mut dyn Sampler) -> Option<(Vec3, Color, f32)> {
+        if self.distribution.integral() <= 0.0 {
+            return None;
+        }
+
+        let (u1, u2) = sampler.next_2d();
+        let ((u, v), pdf) = self.distribution.sample(u1, u2);
+        let sin_theta = (v * PI).sin();
+        if pdf <= 0.0 || sin_theta <= 0.0 {
+            return None;
+        }
+
+        let direction = self.to_world(uv_direction(u, v));
+        let pdf = pdf / (2.0 * PI * PI * sin_theta);
+        Some((direction, self.lookup(u, v), pdf))
+    }
+
+    fn pdf(&self, direction: Vec3) -> f32 {
+        let (u, v) = texture::uv_coords(self.to_local(direction));
+        let sin_theta = (v * PI).sin();
+        if sin_theta <= 0.0 {
+            return 0.0;
+        }
+        self.distribution.pdf(u, v) / (2.0 * PI * PI * sin_theta)
+    }
+}
@@ -1,4 +1,19 @@
-use super::{Point, Ray, Vec3};
+use super::{ray::Differential, Point, Ray, Sampler, Vec3};
+use std::f32::consts::PI;
+
+/// A point on the lens that a point in the scene was connected to, for
+/// tracing light towards the camera.
+pub(crate) struct LensSample {
+    /// Where the scene point appears on the screen, as fractions like `Camera::ray` takes.
+    pub frac_x: f32,
+    pub frac_y: f32,
+    pub point: Point,
+    /// How much light arriving at the lens point from the scene point counts towards the image.
+    pub importance: f32,
+    /// The density with which the lens point was picked, with respect to
+    /// solid angle as seen from the scene point.
+    pub pdf: f32,
+}
 
 pub struct Camera {
     origin: Point,
@@ -7,6 +22,7 @@ pub struct Camera {
     vertical: Vec3,
     u: Vec3,
     v: Vec3,
+    w: Vec3,
     lens_radius: f32,
 }
 
@@ -42,15 +58,133 @@ impl Camera {
             vertical,
             u,
             v,
+            w,
             lens_radius,
         }
     }
 
-    pub fn ray(&self, frac_x: f32, frac_y: f32) -> Ray {
-        let blur = self.lens_radius * Vec3::random_in_unit_disk();
+    pub fn ray(&self, frac_x: f32, frac_y: f32, sampler: &mut dyn Sampler) -> Ray {
+        let blur = self.lens_radius * Vec3::random_in_unit_disk(sampler);
         let offset = self.u * blur.x + self.v * blur.y;
         let target = self.screen_top_left + (self.horizontal * frac_x) - (self.vertical * frac_y);
 
         Ray::new(self.origin + offset, target - self.origin - offset)
     }
+
+    /// Distance of `point` from the camera along its viewing direction.
+    pub fn depth(&self, point: Point) -> f32 {
+        -(point - self.origin).dot(&self.w)
+    }
+
+    /// Expresses a world space direction in camera space, with X pointing
+    /// right, Y up and Z towards the camera.
+    pub fn to_camera_space(&self, direction: Vec3) -> Vec3 {
+        Vec3::new(
+            direction.dot(&self.u),
+            direction.dot(&self.v),
+            direction.dot(&self.w),
+        )
+    }
+
+    /// Like `ray`, but also traces offset rays `dx` and `dy` further across the screen.
+    pub fn ray_differential(
+        &self,
+        frac_x: f32,
+        frac_y: f32,
+        dx: f32,
+        dy: f32,
+        sampler: &mut dyn Sampler,
+    ) -> Ray {
+        let blur = self.lens_radius * Vec3::random_in_unit_disk(sampler);
+        let offset = self.u * blur.x + self.v * blur.y;
+        let origin = self.origin + offset;
+
+        let target = |frac_x: f32, frac_y: f32| {
+            self.screen_top_left + (self.horizontal * frac_x) - (self.vertical * frac_y)
+        };
+
+        let differential = Differential {
+            rx_origin: origin,
+            rx_direction: target(frac_x + dx, frac_y) - origin,
+            ry_origin: origin,
+            ry_direction: target(frac_x, frac_y + dy) - origin,
+        };
+
+        Ray::with_differential(origin, target(frac_x, frac_y) - origin, differential)
+    }
+}
+
+/// Light tracing needs the camera's importance: the weight of a ray from the
+/// lens towards the scene, normalized so the camera rays the render loop
+/// traces have an importance equal to their density.
+impl Camera {
+    /// The direction the camera looks in.
+    pub(crate) fn forward(&self) -> Vec3 {
+        -self.w
+    }
+
+    fn lens_area(&self) -> f32 {
+        if self.lens_radius > 0.0 {
+            PI * self.lens_radius * self.lens_radius
+        } else {
+            1.0
+        }
+    }
+
+    /// Area of the screen, scaled to lie one unit in front of the lens.
+    fn screen_area(&self) -> f32 {
+        let focus_dist = self.focus_dist();
+        self.horizontal.len() * self.vertical.len() / (focus_dist * focus_dist)
+    }
+
+    fn focus_dist(&self) -> f32 {
+        (self.origin - self.screen_top_left).dot(&self.w)
+    }
+
+    /// Where a ray from `lens` in `direction` crosses the screen, as
+    /// fractions, if it does.
+    fn screen_position(&self, lens: Point, direction: Vec3) -> Option<(f32, f32)> {
+        let along = direction.dot(&self.forward());
+        if along <= 0.0 {
+            return None;
+        }
+
+        let offset = lens + direction * (self.focus_dist() / along) - self.screen_top_left;
+        let frac_x = offset.dot(&self.horizontal) / self.horizontal.dot(&self.horizontal);
+        let frac_y = -offset.dot(&self.vertical) / self.vertical.dot(&self.vertical);
+        ((0.0..1.0).contains(&frac_x) && (0.0..1.0).contains(&frac_y)).then_some((frac_x, frac_y))
+    }
+
+    /// The density with which a camera ray from `lens` points in `direction`,
+    /// with respect to solid angle.
+    pub(crate) fn pdf_direction(&self, lens: Point, direction: Vec3) -> f32 {
+        if self.screen_position(lens, direction).is_none() {
+            return 0.0;
+        }
+        let cosine = direction.unit().dot(&self.forward());
+        1.0 / (self.screen_area() * cosine.powi(3))
+    }
+
+    /// Picks a point on the lens to connect `point` to, if `point` is in view.
+    pub(crate) fn sample_lens(
+        &self,
+        point: Point,
+        sampler: &mut dyn Sampler,
+    ) -> Option<LensSample> {
+        let blur = self.lens_radius * Vec3::random_in_unit_disk(sampler);
+        let lens = self.origin + self.u * blur.x + self.v * blur.y;
+
+        let direction = point - lens;
+        let (frac_x, frac_y) = self.screen_position(lens, direction)?;
+        let distance = direction.len();
+        let cosine = direction.dot(&self.forward()) / distance;
+
+        Some(LensSample {
+            frac_x,
+            frac_y,
+            point: lens,
+            importance: 1.0 / (self.screen_area() * self.lens_area() * cosine.powi(4)),
+            pdf: distance * distance / (cosine * self.lens_area()),
+        })
+    }
 }
@@ -0,0 +1,184 @@
+//! Edge-avoiding à-trous wavelet denoising, guided by feature buffers.
+//!
+//! Based on "Edge-Avoiding À-Trous Wavelet Transform for fast Global
+//! Illumination Filtering" by Dammertz et al.
+
+use super::{Color, HdrImage, Pass, RenderResult};
+
+/// The B3 spline used as the filter kernel at every level.
+const KERNEL: [f32; 5] = [1.0 / 16.0, 1.0 / 4.0, 3.0 / 8.0, 1.0 / 4.0, 1.0 / 16.0];
+
+/// Settings for the denoiser. Smaller sigmas preserve more edges but remove less noise.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Denoiser {
+    /// Number of filter levels; each one doubles the filter's radius.
+    pub iterations: usize,
+    /// How strongly differences in (compressed) color stop the filter. Halved every level.
+    pub sigma_color: f32,
+    /// How strongly differences in normals stop the filter.
+    pub sigma_normal: f32,
+    /// How strongly relative differences in depth stop the filter.
+    pub sigma_depth: f32,
+    /// How strongly differences in albedo stop the filter.
+    pub sigma_albedo: f32,
+}
+
+impl Default for Denoiser {
+    fn default() -> Self {
+        Self {
+            iterations: 5,
+            sigma_color: 3.0,
+            sigma_normal: 0.3,
+            sigma_depth: 0.05,
+            sigma_albedo: 0.1,
+        }
+    }
+}
+
+/// The feature buffers that guide the filter.
+struct Features<'a> {
+    albedo: &'a HdrImage,
+    normal: &'a HdrImage,
+    depth: &'a HdrImage,
+}
+
+impl Denoiser {
+    /// Denoises the beauty image of `result`, which needs `Albedo`, `Normal`
+    /// and `Depth` passes. Returns `None` if any of them is missing.
+    pub fn denoise_result(&self, result: &RenderResult) -> Option<HdrImage> {
+        let albedo = result.pass(Pass::Albedo)?;
+        let normal = result.pass(Pass::Normal)?;
+        let depth = result.pass(Pass::Depth)?;
+        Some(self.denoise(&result.beauty, albedo, normal, depth))
+    }
+
+    /// Denoises `image` using feature buffers of the same size. Alpha is left unchanged.
+    ///
+    /// Lighting is filtered separately from surface color, by dividing out the
+    /// albedo before filtering and multiplying it back in afterwards, so
+    /// textures stay sharp.
+    pub fn denoise(
+        &self,
+        image: &HdrImage,
+        albedo: &HdrImage,
+        normal: &HdrImage,
+        depth: &HdrImage,
+    ) -> HdrImage {
+        let (width, height) = (image.width(), image.height());
+        let features = Features {
+            albedo,
+            normal,
+            depth,
+        };
+
+        let mut current = image.clone();
+        for y in 0..height {
+            for x in 0..width {
+                let color = image.get_pixel(x, y) / demodulation(albedo.get_pixel(x, y));
+                current.set_pixel(x, y, color);
+            }
+        }
+
+        for level in 0..self.iterations {
+            let step = 1 << level;
+            let sigma_color = self.sigma_color / (1 << level) as f32;
+            current = self.filter(&current, &features, step, sigma_color);
+        }
+
+        for y in 0..height {
+            for x in 0..width {
+                let color = current.get_pixel(x, y) * demodulation(albedo.get_pixel(x, y));
+                current.set_pixel(x, y, color);
+            }
+        }
+        current
+    }
+
+    fn filter(
+        &self,
+        image: &HdrImage,
+        features: &Features,
+        step: usize,
+        sigma_color: f32,
+    ) -> HdrImage {
+        let (width, height) = (image.width(), image.height());
+        let mut output = image.clone();
+
+        for y in 0..height {
+            for x in 0..width {
+                let color = image.get_pixel(x, y);
+                let albedo = features.albedo.get_pixel(x, y);
+                let normal = features.normal.get_pixel(x, y);
+                let depth = features.depth.get_pixel(x, y).x;
+
+                let mut sum = Color::new(0.0, 0.0, 0.0);
+                let mut weight_sum = 0.0;
+
+                for (j, ky) in KERNEL.iter().enumerate() {
+                    let Some(qy) = offset(y, j, step, height) else {
+                        continue;
+                    };
+                    for (i, kx) in KERNEL.iter().enumerate() {
+                        let Some(qx) = offset(x, i, step, width) else {
+                            continue;
+                        };
+
+                        let q_color = image.get_pixel(qx, qy);
+                        let q_normal = features.normal.get_pixel(qx, qy);
+                        let q_depth = features.depth.get_pixel(qx, qy).x;
+                        let q_albedo = features.albedo.get_pixel(qx, qy);
+
+                        let color_distance = distance_squared(compress(color), compress(q_color));
+                        let normal_distance = distance_squared(normal, q_normal);
+                        let albedo_distance = distance_squared(albedo, q_albedo);
+                        let depth_distance = (depth - q_depth) / depth.abs().max(1e-3);
+
+                        let weight = kx
+                            * ky
+                            * (-color_distance / (sigma_color * sigma_color)
+                                - normal_distance / (self.sigma_normal * self.sigma_normal)
+                                - albedo_distance / (self.sigma_albedo * self.sigma_albedo)
+                                - depth_distance * depth_distance
+                                    / (self.sigma_depth * self.sigma_depth))
+                                .exp();
+
+                        sum = sum + q_color * weight;
+                        weight_sum += weight;
+                    }
+                }
+
+                // The center pixel always has a weight, so this is never zero.
+                output.set_pixel(x, y, sum / weight_sum);
+            }
+        }
+        output
+    }
+}
+
+/// The pixel `index` taps away from `center` in the kernel, if it lies inside the image.
+fn offset(center: usize, index: usize, step: usize, size: usize) -> Option<usize> {
+    let position = center as isize + (index as isize - 2) * step as isize;
+    (0..size as isize)
+        .contains(&position)
+        .then_some(position as usize)
+}
+
+/// The albedo to divide out of a pixel, avoiding blow ups on black surfaces and the background.
+fn demodulation(albedo: Color) -> Color {
+    let channel = |c: f32| if c > 1e-3 { c } else { 1.0 };
+    Color::new(channel(albedo.x), channel(albedo.y), channel(albedo.z))
+}
+
+/// Compresses HDR colors, so bright outliers don't dominate color differences.
+fn compress(color: Color) -> Color {
+    Color::new(
+        color.x / (1.0 + color.x),
+        color.y / (1.0 + color.y),
+        color.z / (1.0 + color.z),
+    )
+}
+
+fn distance_squared(a: Color, b: Color) -> f32 {
+    let d = a - b;
+    d.dot(&d)
+}
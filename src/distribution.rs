@@ -0,0 +1,111 @@
+/// A piecewise constant distribution over `[0, 1)`, used for importance sampling.
+pub(crate) struct Distribution1D {
+    func: Vec<f32>,
+    cdf: Vec<f32>,
+    integral: f32,
+}
+
+impl Distribution1D {
+    pub(crate) fn new(func: Vec<f32>) -> Self {
+        let n = func.len();
+        let mut cdf = Vec::with_capacity(n + 1);
+        cdf.push(0.0);
+        for i in 0..n {
+            cdf.push(cdf[i] + func[i].max(0.0) / n as f32);
+        }
+
+        let integral = cdf[n];
+        for (i, c) in cdf.iter_mut().enumerate() {
+            *c = if integral > 0.0 {
+                *c / integral
+            } else {
+                i as f32 / n as f32
+            };
+        }
+
+        Self {
+            func,
+            cdf,
+            integral,
+        }
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.func.len()
+    }
+
+    pub(crate) fn integral(&self) -> f32 {
+        self.integral
+    }
+
+    /// Maps `x` in `[0, 1)` to a sample, returning the sample, its pdf and the segment it lies in.
+    pub(crate) fn sample(&self, x: f32) -> (f32, f32, usize) {
+        let n = self.len();
+        let index = self.cdf.partition_point(|&c| c <= x).clamp(1, n) - 1;
+
+        let width = self.cdf[index + 1] - self.cdf[index];
+        let offset = if width > 0.0 {
+            (x - self.cdf[index]) / width
+        } else {
+            0.0
+        };
+
+        let pdf = self.pdf(index);
+        (((index as f32 + offset) / n as f32).min(1.0), pdf, index)
+    }
+
+    /// The density of segment `index`.
+    pub(crate) fn pdf(&self, index: usize) -> f32 {
+        if self.integral > 0.0 {
+            self.func[index].max(0.0) / self.integral
+        } else {
+            1.0
+        }
+    }
+}
+
+/// A piecewise constant distribution over `[0, 1)²`, stored as rows of conditional distributions.
+pub(crate) struct Distribution2D {
+    conditional: Vec<Distribution1D>,
+    marginal: Distribution1D,
+}
+
+impl Distribution2D {
+    /// `func` is laid out in row-major order with `width` values per row.
+    pub(crate) fn new(func: &[f32], width: usize, height: usize) -> Self {
+        let conditional: Vec<Distribution1D> = func
+            .chunks_exact(width)
+            .take(height)
+            .map(|row| Distribution1D::new(row.to_vec()))
+            .collect();
+        let marginal = Distribution1D::new(conditional.iter().map(|d| d.integral()).collect());
+
+        Self {
+            conditional,
+            marginal,
+        }
+    }
+
+    pub(crate) fn integral(&self) -> f32 {
+        self.marginal.integral()
+    }
+
+    /// Returns `(x, y)` in `[0, 1)²` and the pdf of the sample.
+    pub(crate) fn sample(&self, u1: f32, u2: f32) -> ((f32, f32), f32) {
+        let (y, pdf_y, row) = self.marginal.sample(u2);
+        let (x, pdf_x, _) = self.conditional[row].sample(u1);
+        ((x, y), pdf_x * pdf_y)
+    }
+
+    pub(crate) fn pdf(&self, x: f32, y: f32) -> f32 {
+        let row = ((y * self.marginal.len() as f32) as usize).min(self.marginal.len() - 1);
+        let conditional = &self.conditional[row];
+        let column = ((x * conditional.len() as f32) as usize).min(conditional.len() - 1);
+
+        if self.marginal.integral() > 0.0 {
+            conditional.func[column].max(0.0) / self.marginal.integral()
+        } else {
+            1.0
+        }
+    }
+}
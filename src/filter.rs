@@ -0,0 +1,292 @@
+//! Pixel reconstruction filters, and the film that samples are splatted into.
+
+use super::{Color, Pass, RenderResult};
+use std::f32::consts::PI;
+
+/// How samples are weighted by their distance from a pixel's center, in pixels.
+///
+/// Samples contribute to every pixel within `radius` of them, and each pixel
+/// is the weighted average of the samples around it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Filter {
+    /// Equal weights. A radius of 0.5 averages the samples inside each pixel.
+    Box { radius: f32 },
+    /// Weights falling off linearly to zero at the radius.
+    Tent { radius: f32 },
+    /// A Gaussian with standard deviation `sigma`, shifted to reach zero at the radius.
+    Gaussian { radius: f32, sigma: f32 },
+    /// The Mitchell-Netravali cubic. `b = c = 1/3` is the recommended balance
+    /// between blurring and ringing.
+    Mitchell { radius: f32, b: f32, c: f32 },
+    /// A sinc windowed by a wider sinc, reaching zero at the radius.
+    Lanczos { radius: f32 },
+}
+
+impl Default for Filter {
+    fn default() -> Self {
+        Filter::Box { radius: 0.5 }
+    }
+}
+
+impl Filter {
+    pub fn radius(&self) -> f32 {
+        match *self {
+            Filter::Box { radius }
+            | Filter::Tent { radius }
+            | Filter::Gaussian { radius, .. }
+            | Filter::Mitchell { radius, .. }
+            | Filter::Lanczos { radius } => radius,
+        }
+    }
+
+    /// The weight of a sample at offset `(x, y)` from a pixel's center.
+    pub fn evaluate(&self, x: f32, y: f32) -> f32 {
+        self.evaluate_1d(x) * self.evaluate_1d(y)
+    }
+
+    fn evaluate_1d(&self, x: f32) -> f32 {
+        let x = x.abs();
+        match *self {
+            Filter::Box { radius } => (x <= radius) as u8 as f32,
+            Filter::Tent { radius } => (radius - x).max(0.0),
+            Filter::Gaussian { radius, sigma } => {
+                let gaussian = |x: f32| (-x * x / (2.0 * sigma * sigma)).exp();
+                (gaussian(x) - gaussian(radius)).max(0.0)
+            }
+            Filter::Mitchell { radius, b, c } => {
+                let x = 2.0 * x / radius;
+                if x < 1.0 {
+                    ((12.0 - 9.0 * b - 6.0 * c) * x.powi(3)
+                        + (-18.0 + 12.0 * b + 6.0 * c) * x * x
+                        + (6.0 - 2.0 * b))
+                        / 6.0
+                } else if x < 2.0 {
+                    ((-b - 6.0 * c) * x.powi(3)
+                        + (6.0 * b + 30.0 * c) * x * x
+                        + (-12.0 * b - 48.0 * c) * x
+                        + (8.0 * b + 24.0 * c))
+                        / 6.0
+                } else {
+                    0.0
+                }
+            }
+            Filter::Lanczos { radius } => {
+                if x < radius {
+                    sinc(x) * sinc(x / radius)
+                } else {
+                    0.0
+                }
+            }
+        }
+    }
+
+    /// How many pixels outside a region its samples can reach.
+    pub(crate) fn margin(&self) -> usize {
+        (self.radius() + 0.5).ceil() as usize
+    }
+}
+
+fn sinc(x: f32) -> f32 {
+    if x < 1e-5 {
+        1.0
+    } else {
+        (PI * x).sin() / (PI * x)
+    }
+}
+
+/// Weighted sums of filtered samples, for a region of the image.
+///
+/// Each pixel holds several layers of colors, filtered identically: the beauty
+/// image followed by the lighting passes.
+///
+/// Films of blocks are merged in tile order, so the sums are the same whatever
+/// the thread count. How the image is split into tiles can change the order
+/// in which a pixel's sums are added, where a filter or splats reach across
+/// blocks, and so the last bit of a few pixels. Sums are kept in double
+/// precision to make that rare.
+///
+/// Splats, which estimate light reaching the image anywhere rather than at a
+/// pixel's own samples, are summed separately and added after filtering.
+pub(crate) struct Film {
+    x: isize,
+    y: isize,
+    width: usize,
+    height: usize,
+    layers: usize,
+    sums: Vec<[f64; 3]>,
+    alpha: Vec<f64>,
+    weights: Vec<f64>,
+    splats: Vec<[f64; 3]>,
+}
+
+impl Film {
+    /// Creates an empty film covering `width` by `height` pixels from `(x, y)`.
+    pub fn new(x: isize, y: isize, width: usize, height: usize, layers: usize) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+            layers,
+            sums: vec![[0.0; 3]; width * height * layers],
+            alpha: vec![0.0; width * height],
+            weights: vec![0.0; width * height],
+            splats: vec![[0.0; 3]; width * height * layers],
+        }
+    }
+
+    /// Adds a sample at `(x, y)` in image space to every pixel within the filter's radius.
+    pub fn add_sample(&mut self, filter: &Filter, x: f32, y: f32, values: &[Color], alpha: f32) {
+        let radius = filter.radius();
+
+        // Pixels whose centers lie in (x - radius, x + radius].
+        let (x_min, x_max) = (
+            (x - radius - 0.5).floor() as isize + 1,
+            (x + radius - 0.5).floor() as isize,
+        );
+        let (y_min, y_max) = (
+            (y - radius - 0.5).floor() as isize + 1,
+            (y + radius - 0.5).floor() as isize,
+        );
+
+        for py in y_min.max(self.y)..=y_max.min(self.y + self.height as isize - 1) {
+            for px in x_min.max(self.x)..=x_max.min(self.x + self.width as isize - 1) {
+                let weight = filter.evaluate(px as f32 + 0.5 - x, py as f32 + 0.5 - y) as f64;
+                if weight == 0.0 {
+                    continue;
+                }
+
+                let index = (py - self.y) as usize * self.width + (px - self.x) as usize;
+                for (layer, value) in values.iter().enumerate() {
+                    let sum = &mut self.sums[index * self.layers + layer];
+                    sum[0] += value.x as f64 * weight;
+                    sum[1] += value.y as f64 * weight;
+                    sum[2] += value.z as f64 * weight;
+                }
+                self.alpha[index] += alpha as f64 * weight;
+                self.weights[index] += weight;
+            }
+        }
+    }
+
+    /// Adds splats to the pixels they land in.
+    pub fn add_splats(&mut self, splats: &Splats) {
+        let values = splats.values.chunks_exact(self.layers);
+        for (&(x, y), values) in splats.positions.iter().zip(values) {
+            let (px, py) = (x.floor() as isize - self.x, y.floor() as isize - self.y);
+            if !(0..self.width as isize).contains(&px) || !(0..self.height as isize).contains(&py) {
+                continue;
+            }
+
+            let index = py as usize * self.width + px as usize;
+            for (layer, value) in values.iter().enumerate() {
+                let sum = &mut self.splats[index * self.layers + layer];
+                sum[0] += value.x as f64;
+                sum[1] += value.y as f64;
+                sum[2] += value.z as f64;
+            }
+        }
+    }
+
+    /// Adds the sums of another film to the pixels both films cover.
+    pub fn merge(&mut self, other: &Film) {
+        for oy in 0..other.height {
+            for ox in 0..other.width {
+                let (px, py) = (other.x + ox as isize, other.y + oy as isize);
+                if !(self.x..self.x + self.width as isize).contains(&px)
+                    || !(self.y..self.y + self.height as isize).contains(&py)
+                {
+                    continue;
+                }
+
+                let index = (py - self.y) as usize * self.width + (px - self.x) as usize;
+                let other_index = oy * other.width + ox;
+                for layer in 0..self.layers {
+                    let sum = &mut self.sums[index * self.layers + layer];
+                    let other_sum = other.sums[other_index * self.layers + layer];
+                    for (s, o) in sum.iter_mut().zip(other_sum) {
+                        *s += o;
+                    }
+
+                    let splat = &mut self.splats[index * self.layers + layer];
+                    let other_splat = other.splats[other_index * self.layers + layer];
+                    for (s, o) in splat.iter_mut().zip(other_splat) {
+                        *s += o;
+                    }
+                }
+                self.alpha[index] += other.alpha[other_index];
+                self.weights[index] += other.weights[other_index];
+            }
+        }
+    }
+
+    /// Writes the filtered beauty image and `lighting` passes into `result`,
+    /// whose top left corner is at `(x, y)` in image space, adding splats
+    /// multiplied by `splat_scale`.
+    pub fn develop(
+        &self,
+        result: &mut RenderResult,
+        x: usize,
+        y: usize,
+        lighting: &[Pass],
+        splat_scale: f64,
+    ) {
+        for ry in 0..result.beauty.height() {
+            for rx in 0..result.beauty.width() {
+                let (px, py) = ((x + rx) as isize - self.x, (y + ry) as isize - self.y);
+                let index = py as usize * self.width + px as usize;
+
+                // Pixels no sample reached, such as those of a cancelled render, are left empty.
+                let weight = self.weights[index];
+                if weight == 0.0 {
+                    result.beauty.set_pixel(rx, ry, Color::new(0.0, 0.0, 0.0));
+                    result.beauty.set_alpha(rx, ry, 0.0);
+                    continue;
+                }
+
+                let layer = |layer: usize| {
+                    let [r, g, b] = self.sums[index * self.layers + layer];
+                    let [sr, sg, sb] = self.splats[index * self.layers + layer];
+                    Color::new(
+                        (r / weight + sr * splat_scale) as f32,
+                        (g / weight + sg * splat_scale) as f32,
+                        (b / weight + sb * splat_scale) as f32,
+                    )
+                };
+                result.beauty.set_pixel(rx, ry, layer(0));
+                let alpha = (self.alpha[index] / weight).clamp(0.0, 1.0) as f32;
+                result.beauty.set_alpha(rx, ry, alpha);
+
+                for (i, pass) in lighting.iter().enumerate() {
+                    if let Some(image) = result.pass_mut(*pass) {
+                        image.set_pixel(rx, ry, layer(i + 1));
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Splats collected while rendering a tile, which may land anywhere in the image.
+pub(crate) struct Splats {
+    layers: usize,
+    positions: Vec<(f32, f32)>,
+    values: Vec<Color>,
+}
+
+impl Splats {
+    pub fn new(layers: usize) -> Self {
+        Self {
+            layers,
+            positions: Vec::new(),
+            values: Vec::new(),
+        }
+    }
+
+    /// Adds a splat at `(x, y)` in image space, with a value for each layer.
+    pub fn add(&mut self, x: f32, y: f32, values: &[Color]) {
+        debug_assert_eq!(values.len(), self.layers);
+        self.positions.push((x, y));
+        self.values.extend_from_slice(values);
+    }
+}
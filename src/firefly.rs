@@ -0,0 +1,154 @@
+//! Suppressing fireflies: rare, very bright samples that take many more
+//! samples to average away than the rest of the image.
+//!
+//! Both methods darken the brightest light in exchange, so they are biased.
+
+use super::filter::Film;
+use super::integrator::Contribution;
+use super::{Color, Filter};
+
+/// Limits on the light of each sample, by how many bounces it took. Light
+/// emitted by the surfaces camera rays hit (or the background) isn't limited.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Clamp {
+    /// Maximum of each color channel of light from a single bounce.
+    pub direct: f32,
+    /// Maximum of each color channel of light from more bounces, where most
+    /// fireflies come from.
+    pub indirect: f32,
+}
+
+impl Default for Clamp {
+    fn default() -> Self {
+        Self {
+            direct: f32::INFINITY,
+            indirect: 10.0,
+        }
+    }
+}
+
+impl Clamp {
+    /// Clamps light that is multiplied by `scale` before reaching a pixel as
+    /// one of its samples, as light traced from emitters is.
+    pub(crate) fn apply(&self, contribution: Contribution, scale: f32) -> Contribution {
+        Contribution {
+            emitted: contribution.emitted,
+            direct: clamp(contribution.direct, self.direct / scale),
+            indirect: clamp(contribution.indirect, self.indirect / scale),
+        }
+    }
+}
+
+/// Scales `color` down, keeping its hue, until no channel exceeds `limit`.
+fn clamp(color: Color, limit: f32) -> Color {
+    let max = color.x.max(color.y).max(color.z);
+    if max > limit {
+        color * (limit / max)
+    } else {
+        color
+    }
+}
+
+/// How the samples of each pixel are combined.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Accumulation {
+    /// Their mean, which converges to the correct image.
+    #[default]
+    Mean,
+    /// The median of the means of `groups` groups of samples, which few
+    /// outliers can move. A pixel whose mean is brighter than that is scaled
+    /// down to match, so a firefly only stays if it shows up in most groups.
+    ///
+    /// Only the samples of one render are grouped, so in a progressive render
+    /// each pass is grouped on its own, and passes of a single sample are
+    /// left as they are.
+    ///
+    /// Light splatted to the image from elsewhere, as bidirectional and
+    /// Metropolis integrators do, is left alone.
+    MedianOfMeans { groups: usize },
+}
+
+/// The samples of a pixel, held back until all of them are known, for
+/// accumulation modes that look at them together. `Accumulation::Mean` adds
+/// samples to the film straight away instead.
+pub(crate) struct PixelSamples {
+    layers: usize,
+    /// Position and alpha of each sample.
+    samples: Vec<(f32, f32, f32)>,
+    values: Vec<Color>,
+    scaled: Vec<Color>,
+}
+
+impl PixelSamples {
+    pub fn new(layers: usize) -> Self {
+        Self {
+            layers,
+            samples: Vec::new(),
+            values: Vec::new(),
+            scaled: vec![Color::new(0.0, 0.0, 0.0); layers],
+        }
+    }
+
+    pub fn push(&mut self, x: f32, y: f32, values: &[Color], alpha: f32) {
+        self.samples.push((x, y, alpha));
+        self.values.extend_from_slice(values);
+    }
+
+    /// Adds the samples to `film` as `accumulation` combines them, and clears them.
+    pub fn flush(&mut self, film: &mut Film, filter: &Filter, accumulation: Accumulation) {
+        if self.samples.is_empty() {
+            return;
+        }
+        let scale = match accumulation {
+            Accumulation::Mean => 1.0,
+            Accumulation::MedianOfMeans { groups } => self.median_of_means_scale(groups),
+        };
+
+        let values = self.values.chunks_exact(self.layers);
+        for (&(x, y, alpha), values) in self.samples.iter().zip(values) {
+            for (scaled, value) in self.scaled.iter_mut().zip(values) {
+                *scaled = *value * scale;
+            }
+            film.add_sample(filter, x, y, &self.scaled, alpha);
+        }
+
+        self.samples.clear();
+        self.values.clear();
+    }
+
+    /// How much the samples are scaled by for their mean luminance to be at
+    /// most the median of the means of `groups` groups of them.
+    fn median_of_means_scale(&self, groups: usize) -> f32 {
+        let count = self.samples.len();
+        let groups = groups.min(count);
+        if groups < 2 {
+            return 1.0;
+        }
+
+        let luminance: Vec<f32> = self
+            .values
+            .chunks_exact(self.layers)
+            .map(|values| values[0].luminance())
+            .collect();
+        let mean = luminance.iter().sum::<f32>() / count as f32;
+
+        let mut means: Vec<f32> = (0..groups)
+            .map(|group| {
+                let group = &luminance[group * count / groups..(group + 1) * count / groups];
+                group.iter().sum::<f32>() / group.len() as f32
+            })
+            .collect();
+        means.sort_unstable_by(f32::total_cmp);
+        let median = if groups.is_multiple_of(2) {
+            (means[groups / 2 - 1] + means[groups / 2]) / 2.0
+        } else {
+            means[groups / 2]
+        };
+
+        if mean > median && median >= 0.0 {
+            median / mean
+        } else {
+            1.0
+        }
+    }
+}
@@ -0,0 +1,511 @@
+//! Path guiding, which learns where light comes from over the passes of a
+//! progressive render and sends rays scattered off diffuse surfaces that way.
+//!
+//! Based on "Practical Path Guiding for Efficient Light-Transport Simulation"
+//! by Müller et al.
+
+use super::{Color, HitRecord, Point, Ray, Sampler, Scene, Vec3};
+use std::cell::RefCell;
+use std::f32::consts::PI;
+use std::mem;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
+/// Settings for path guiding.
+///
+/// Guides are learned from the paths of each pass, so they are only as good
+/// as the number of samples per pass allows.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PathGuiding {
+    /// Fraction of rays scattered off diffuse surfaces that are sampled by
+    /// their material, rather than towards where light was found.
+    pub bsdf_fraction: f32,
+    /// Number of rays a region of the scene records in a pass before it is
+    /// split in two, learning separately about each half.
+    pub spatial_threshold: usize,
+    /// Fraction of a region's light that a range of directions must receive
+    /// before it is split in four.
+    pub directional_threshold: f32,
+}
+
+impl Default for PathGuiding {
+    fn default() -> Self {
+        Self {
+            bsdf_fraction: 0.5,
+            spatial_threshold: 12000,
+            directional_threshold: 0.01,
+        }
+    }
+}
+
+/// Maximum depth of the directional quadtrees.
+const MAX_DEPTH: usize = 20;
+
+thread_local! {
+    /// Light recorded by the block this thread is rendering, held back until
+    /// the block is merged, so that blocks are added to the guide in tile
+    /// order whichever thread rendered them.
+    static RECORDED: RefCell<Vec<Recorded>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Light found by a guided ray, waiting to be added to the guide.
+pub(crate) struct Recorded {
+    region: usize,
+    direction: Vec3,
+    /// Luminance of the light over the pdf the ray was scattered with.
+    value: f64,
+}
+
+/// Takes the light recorded on this thread since it was last taken.
+pub(crate) fn take_recorded() -> Vec<Recorded> {
+    RECORDED.with(|recorded| mem::take(&mut *recorded.borrow_mut()))
+}
+
+/// The light learned so far: a binary tree splitting the scene's bounds in
+/// half along alternating axes, with a quadtree over directions in each leaf.
+pub(crate) struct Guide {
+    settings: PathGuiding,
+    /// Corner and size of the cube the tree covers.
+    min: Point,
+    size: f32,
+    nodes: Vec<SpatialNode>,
+    regions: Vec<Region>,
+}
+
+enum SpatialNode {
+    Split([usize; 2]),
+    Region(usize),
+}
+
+/// A leaf of the spatial tree.
+struct Region {
+    /// Light recorded in the previous pass, which rays are guided by.
+    sampling: DirectionTree,
+    /// Light recorded in this pass.
+    recording: DirectionTree,
+    records: AtomicUsize,
+}
+
+impl Guide {
+    /// A guide that hasn't learned anything, covering the bounds of `scene`.
+    pub(crate) fn new(scene: &Scene, settings: PathGuiding) -> Self {
+        let bounds = scene
+            .objects()
+            .iter()
+            .filter_map(|object| object.bounds())
+            .reduce(|(a_min, a_max), (b_min, b_max)| {
+                let min = Vec3::new(
+                    a_min.x.min(b_min.x),
+                    a_min.y.min(b_min.y),
+                    a_min.z.min(b_min.z),
+                );
+                let max = Vec3::new(
+                    a_max.x.max(b_max.x),
+                    a_max.y.max(b_max.y),
+                    a_max.z.max(b_max.z),
+                );
+                (min, max)
+            });
+        let (min, max) = bounds.unwrap_or((Vec3::new(-1.0, -1.0, -1.0), Vec3::new(1.0, 1.0, 1.0)));
+        let extent = max - min;
+
+        Self {
+            settings,
+            min,
+            size: extent.x.max(extent.y).max(extent.z).max(f32::MIN_POSITIVE),
+            nodes: vec![SpatialNode::Region(0)],
+            regions: vec![Region {
+                sampling: DirectionTree::new(),
+                recording: DirectionTree::new(),
+                records: AtomicUsize::new(0),
+            }],
+        }
+    }
+
+    /// What has been learned about light arriving at `point`.
+    pub(crate) fn at(&self, point: Point) -> Guided<'_> {
+        let mut min = [self.min.x, self.min.y, self.min.z];
+        let mut size = [self.size; 3];
+        let mut index = 0;
+        for depth in 0.. {
+            match self.nodes[index] {
+                SpatialNode::Region(region) => {
+                    return Guided {
+                        index: region,
+                        region: &self.regions[region],
+                        bsdf_fraction: self.settings.bsdf_fraction,
+                    };
+                }
+                SpatialNode::Split(children) => {
+                    let axis = depth % 3;
+                    size[axis] /= 2.0;
+                    if point.axis(axis) < min[axis] + size[axis] {
+                        index = children[0];
+                    } else {
+                        index = children[1];
+                        min[axis] += size[axis];
+                    }
+                }
+            }
+        }
+        unreachable!()
+    }
+
+    /// Adds light recorded with `Guided::record` to the regions it was found in.
+    pub(crate) fn add(&self, recorded: &[Recorded]) {
+        for recorded in recorded {
+            let region = &self.regions[recorded.region];
+            region.records.fetch_add(1, Ordering::Relaxed);
+            if recorded.value > 0.0 && recorded.value.is_finite() {
+                region.recording.add(recorded.direction, recorded.value);
+            }
+        }
+    }
+
+    /// The guide for the next pass: guiding by the light recorded in this
+    /// pass, with regions that recorded many rays split up, and directions
+    /// that received much of their region's light subdivided further.
+    pub(crate) fn refined(&self) -> Self {
+        let mut guide = Self {
+            settings: self.settings,
+            min: self.min,
+            size: self.size,
+            nodes: Vec::new(),
+            regions: Vec::new(),
+        };
+        self.refine_node(0, &mut guide);
+        guide
+    }
+
+    fn refine_node(&self, index: usize, guide: &mut Guide) -> usize {
+        match self.nodes[index] {
+            SpatialNode::Split(children) => {
+                let new = guide.nodes.len();
+                guide.nodes.push(SpatialNode::Split([0, 0]));
+                let children = children.map(|child| self.refine_node(child, guide));
+                guide.nodes[new] = SpatialNode::Split(children);
+                new
+            }
+            SpatialNode::Region(region) => {
+                let region = &self.regions[region];
+                let records = region.records.load(Ordering::Relaxed);
+                guide.split(&region.recording, records)
+            }
+        }
+    }
+
+    /// Adds a region that learned `light` from `records` rays, splitting it
+    /// in half until each part recorded few enough rays.
+    fn split(&mut self, light: &DirectionTree, records: usize) -> usize {
+        let index = self.nodes.len();
+        if records > self.settings.spatial_threshold {
+            self.nodes.push(SpatialNode::Split([0, 0]));
+            let children = [(); 2].map(|_| self.split(light, records / 2));
+            self.nodes[index] = SpatialNode::Split(children);
+        } else {
+            self.nodes.push(SpatialNode::Region(self.regions.len()));
+            self.regions.push(Region {
+                sampling: light.copy(),
+                recording: light.refined(self.settings.directional_threshold),
+                records: AtomicUsize::new(0),
+            });
+        }
+        index
+    }
+}
+
+/// What has been learned about light arriving at a point, for guiding rays
+/// scattered off a diffuse surface there.
+pub(crate) struct Guided<'a> {
+    index: usize,
+    region: &'a Region,
+    bsdf_fraction: f32,
+}
+
+impl Guided<'_> {
+    /// Whether the region has learned anything to guide rays by.
+    fn is_trained(&self) -> bool {
+        self.region.sampling.total() > 0.0
+    }
+
+    /// Scatters a ray off a diffuse surface, sampled either by its material or
+    /// by the learned light, returning it with its weight and the pdf it was
+    /// sampled with, like `Material::scatter` and `Material::eval`.
+    pub(crate) fn scatter(
+        &self,
+        ray: Ray,
+        record: HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(Ray, Color, Option<f32>)> {
+        let material = record.material;
+        let scatter = |sampler: &mut dyn Sampler| {
+            let (scattered, weight) = material.scatter(ray, record, sampler)?;
+            let pdf = material
+                .eval(ray, &record, scattered.direction)
+                .map(|(_, pdf)| pdf);
+            Some((scattered, weight, pdf))
+        };
+
+        if !self.is_trained() {
+            return scatter(sampler);
+        }
+
+        let direction = if sampler.next_1d() < self.bsdf_fraction {
+            material.scatter(ray, record, sampler)?.0.direction
+        } else {
+            self.region.sampling.sample(sampler)
+        };
+        let Some((f, bsdf_pdf)) = material.eval(ray, &record, direction) else {
+            return scatter(sampler);
+        };
+
+        let pdf = self.pdf(bsdf_pdf, direction);
+        let weight = if pdf > 0.0 {
+            f / pdf
+        } else {
+            Color::new(0.0, 0.0, 0.0)
+        };
+        Some((record.spawn_ray(direction), weight, Some(pdf)))
+    }
+
+    /// The pdf with which `scatter` picks `direction`, given the pdf of the
+    /// material picking it.
+    pub(crate) fn pdf(&self, bsdf_pdf: f32, direction: Vec3) -> f32 {
+        if !self.is_trained() {
+            return bsdf_pdf;
+        }
+        let guide_pdf = self.region.sampling.pdf(direction);
+        self.bsdf_fraction * bsdf_pdf + (1.0 - self.bsdf_fraction) * guide_pdf
+    }
+
+    /// Records light arriving from `direction`, found by a ray that was
+    /// scattered that way with `pdf`. It is added to the guide with
+    /// `Guide::add`, once taken with `take_recorded`.
+    pub(crate) fn record(&self, direction: Vec3, radiance: Color, pdf: Option<f32>) {
+        let Some(pdf) = pdf.filter(|pdf| *pdf > 0.0) else {
+            return;
+        };
+        let recorded = Recorded {
+            region: self.index,
+            direction,
+            value: (radiance.luminance() / pdf) as f64,
+        };
+        RECORDED.with(|buffer| buffer.borrow_mut().push(recorded));
+    }
+}
+
+/// A distribution over directions, as a quadtree over the unit square that
+/// directions are mapped to by cylindrical coordinates, which preserve area.
+struct DirectionTree {
+    nodes: Vec<DirectionNode>,
+}
+
+#[derive(Default)]
+struct DirectionNode {
+    /// Indices of the children of each quadrant, or 0 for leaves.
+    children: [u32; 4],
+    /// Light received in each quadrant, as the bits of `f64`s so that light
+    /// can be added while the threads rendering share the guide.
+    sums: [AtomicU64; 4],
+}
+
+impl DirectionNode {
+    fn sum(&self, quadrant: usize) -> f64 {
+        f64::from_bits(self.sums[quadrant].load(Ordering::Relaxed))
+    }
+
+    fn sums(&self) -> [f64; 4] {
+        [0, 1, 2, 3].map(|quadrant| self.sum(quadrant))
+    }
+
+    fn total(&self) -> f64 {
+        self.sums().iter().sum()
+    }
+
+    fn add(&self, quadrant: usize, value: f64) {
+        let sum = &self.sums[quadrant];
+        let mut current = sum.load(Ordering::Relaxed);
+        loop {
+            let new = (f64::from_bits(current) + value).to_bits();
+            match sum.compare_exchange_weak(current, new, Ordering::Relaxed, Ordering::Relaxed) {
+                Ok(_) => break,
+                Err(actual) => current = actual,
+            }
+        }
+    }
+}
+
+impl DirectionTree {
+    fn new() -> Self {
+        Self {
+            nodes: vec![DirectionNode::default()],
+        }
+    }
+
+    fn copy(&self) -> Self {
+        let nodes = self
+            .nodes
+            .iter()
+            .map(|node| DirectionNode {
+                children: node.children,
+                sums: node.sums().map(|sum| AtomicU64::new(sum.to_bits())),
+            })
+            .collect();
+        Self { nodes }
+    }
+
+    fn total(&self) -> f64 {
+        self.nodes[0].total()
+    }
+
+    fn add(&self, direction: Vec3, value: f64) {
+        let (mut u, mut v) = to_square(direction);
+        let mut index = 0;
+        loop {
+            let quadrant = quadrant(&mut u, &mut v);
+            let node = &self.nodes[index];
+            node.add(quadrant, value);
+            match node.children[quadrant] {
+                0 => break,
+                child => index = child as usize,
+            }
+        }
+    }
+
+    fn pdf(&self, direction: Vec3) -> f32 {
+        let (mut u, mut v) = to_square(direction);
+        let mut pdf = 1.0;
+        let mut index = 0;
+        loop {
+            let node = &self.nodes[index];
+            let total = node.total();
+            if total <= 0.0 {
+                break;
+            }
+            let quadrant = quadrant(&mut u, &mut v);
+            pdf *= 4.0 * node.sum(quadrant) / total;
+            match node.children[quadrant] {
+                0 => break,
+                child => index = child as usize,
+            }
+        }
+        pdf as f32 / (4.0 * PI)
+    }
+
+    fn sample(&self, sampler: &mut dyn Sampler) -> Vec3 {
+        let (mut x, mut y) = (0.0, 0.0);
+        let mut size = 1.0;
+        let mut index = 0;
+        loop {
+            let node = &self.nodes[index];
+            let sums = node.sums();
+
+            // Picks a quadrant by its light, falling back to the last one
+            // with any light if rounding goes past the end.
+            let mut target = sampler.next_1d() as f64 * node.total();
+            let mut quadrant = 0;
+            for (i, sum) in sums.iter().enumerate() {
+                if *sum > 0.0 {
+                    quadrant = i;
+                    if target < *sum {
+                        break;
+                    }
+                    target -= sum;
+                }
+            }
+
+            size /= 2.0;
+            x += size * (quadrant % 2) as f32;
+            y += size * (quadrant / 2) as f32;
+            match node.children[quadrant] {
+                0 => break,
+                child => index = child as usize,
+            }
+        }
+
+        let (u, v) = sampler.next_2d();
+        from_square(x + u * size, y + v * size)
+    }
+
+    /// A tree with no light recorded, subdividing the quadrants of this tree
+    /// that received more than `threshold` of its light, and merging those
+    /// that received less.
+    fn refined(&self, threshold: f32) -> Self {
+        let mut tree = Self::new();
+        let limit = self.total() * threshold as f64;
+        if limit > 0.0 {
+            self.refine_node(Some(0), self.nodes[0].sums(), 0, limit, 1, &mut tree);
+        }
+        tree
+    }
+
+    /// Subdivides the quadrants of node `new` of `tree`, which received
+    /// `sums` in this tree, at node `old` if it has one here.
+    fn refine_node(
+        &self,
+        old: Option<usize>,
+        sums: [f64; 4],
+        new: usize,
+        limit: f64,
+        depth: usize,
+        tree: &mut Self,
+    ) {
+        if depth >= MAX_DEPTH {
+            return;
+        }
+        for (quadrant, sum) in sums.into_iter().enumerate() {
+            if sum <= limit {
+                continue;
+            }
+            let old_child = old
+                .map(|old| self.nodes[old].children[quadrant] as usize)
+                .filter(|child| *child != 0);
+            // Light is assumed to be spread evenly over leaves of this tree.
+            let child_sums = match old_child {
+                Some(child) => self.nodes[child].sums(),
+                None => [sum / 4.0; 4],
+            };
+
+            let child = tree.nodes.len();
+            tree.nodes.push(DirectionNode::default());
+            tree.nodes[new].children[quadrant] = child as u32;
+            self.refine_node(old_child, child_sums, child, limit, depth + 1, tree);
+        }
+    }
+}
+
+/// The quadrant of the unit square that `(u, v)` lies in, moving `(u, v)` to
+/// the same position within the quadrant scaled up to the unit square.
+fn quadrant(u: &mut f32, v: &mut f32) -> usize {
+    let mut quadrant = 0;
+    *u *= 2.0;
+    *v *= 2.0;
+    if *u >= 1.0 {
+        *u -= 1.0;
+        quadrant += 1;
+    }
+    if *v >= 1.0 {
+        *v -= 1.0;
+        quadrant += 2;
+    }
+    quadrant
+}
+
+fn to_square(direction: Vec3) -> (f32, f32) {
+    let direction = direction.unit();
+    let cos_theta = direction.z.clamp(-1.0, 1.0);
+    let mut phi = direction.y.atan2(direction.x);
+    if phi < 0.0 {
+        phi += 2.0 * PI;
+    }
+    let below_one = 1.0 - f32::EPSILON;
+    let u = ((cos_theta + 1.0) / 2.0).clamp(0.0, below_one);
+    let v = (phi / (2.0 * PI)).clamp(0.0, below_one);
+    (u, v)
+}
+
+fn from_square(u: f32, v: f32) -> Vec3 {
+    let cos_theta = 2.0 * u - 1.0;
+    let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+    let phi = 2.0 * PI * v;
+    Vec3::new(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta)
+}
@@ -1,4 +1,8 @@
-use super::Color;
+use super::tonemap::{linear_to_srgb, srgb_to_linear};
+use super::{Color, OutputSettings};
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter};
+use std::path::Path;
 
 #[derive(Debug, Clone, Copy)]
 pub struct Pixel {
@@ -8,11 +12,13 @@ pub struct Pixel {
 }
 
 impl Pixel {
+    /// Encodes a linear color with the sRGB transfer function, clipping it to `[0, 1]`.
     pub fn from_color(color: Color) -> Pixel {
+        let encode = |c: f32| (linear_to_srgb(c.clamp(0.0, 1.0)) * 255.0).round() as u8;
         Pixel {
-            r: (color.x.sqrt() * 255.999) as u8,
-            g: (color.y.sqrt() * 255.999) as u8,
-            b: (color.z.sqrt() * 255.999) as u8,
+            r: encode(color.x),
+            g: encode(color.y),
+            b: encode(color.z),
         }
     }
 }
@@ -38,6 +44,14 @@ impl Image {
         }
     }
 
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
     pub fn get_pixel(&self, x: usize, y: usize) -> Pixel {
         assert!(y < self.height);
         assert!(x < self.width);
@@ -49,4 +63,167 @@ impl Image {
         assert!(x < self.width);
         self.data[y * self.width + x] = pixel;
     }
+
+    /// Saves the image, choosing the format from the file extension.
+    ///
+    /// Supports `.png`, `.ppm` and `.bmp` files.
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let path = path.as_ref();
+
+        match extension(path).as_deref() {
+            Some("png") => crate::io::png::write(BufWriter::new(File::create(path)?), self),
+            Some("ppm") => crate::io::ppm::write(BufWriter::new(File::create(path)?), self),
+            Some("bmp") => {
+                let mut bitmap = bmp::Image::new(self.width as u32, self.height as u32);
+                for (x, y) in bitmap.coordinates() {
+                    let Pixel { r, g, b } = self.get_pixel(x as usize, y as usize);
+                    bitmap.set_pixel(x, y, bmp::Pixel { r, g, b });
+                }
+                bitmap.save(path)
+            }
+            _ => Err(unsupported(path)),
+        }
+    }
+}
+
+/// An image storing linear, floating point colors and an alpha channel.
+#[derive(Debug, Clone)]
+pub struct HdrImage {
+    width: usize,
+    height: usize,
+    data: Vec<Color>,
+    alpha: Vec<f32>,
+}
+
+impl HdrImage {
+    /// Creates a black, fully opaque image.
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            data: vec![Color::new(0.0, 0.0, 0.0); width * height],
+            alpha: vec![1.0; width * height],
+        }
+    }
+
+    /// Loads an image, choosing the format from the file extension.
+    ///
+    /// Supports Radiance `.hdr` and `.pfm` files, as well as 8-bit `.bmp`
+    /// files, which are converted from sRGB to linear colors.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref();
+        let mut reader = BufReader::new(File::open(path)?);
+
+        match extension(path).as_deref() {
+            Some("hdr") => crate::io::hdr::read(reader),
+            Some("pfm") => crate::io::pfm::read(reader),
+            Some("bmp") => {
+                let bitmap = bmp::from_reader(&mut reader)
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+
+                let (width, height) = (bitmap.get_width() as usize, bitmap.get_height() as usize);
+                let mut image = Self::new(width, height);
+                for (x, y) in bitmap.coordinates() {
+                    let bmp::Pixel { r, g, b } = bitmap.get_pixel(x, y);
+                    let color = Color::new(
+                        srgb_to_linear(r as f32 / 255.0),
+                        srgb_to_linear(g as f32 / 255.0),
+                        srgb_to_linear(b as f32 / 255.0),
+                    );
+                    image.set_pixel(x as usize, y as usize, color);
+                }
+                Ok(image)
+            }
+            _ => Err(unsupported(path)),
+        }
+    }
+
+    /// Saves the linear image, choosing the format from the file extension.
+    ///
+    /// Supports `.pfm` and `.exr` files; only the latter stores alpha. Use
+    /// `to_image` first to save in an 8-bit format.
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let path = path.as_ref();
+
+        match extension(path).as_deref() {
+            Some("pfm") => crate::io::pfm::write(BufWriter::new(File::create(path)?), self),
+            Some("exr") => {
+                let r: Vec<f32> = self.data.iter().map(|c| c.x).collect();
+                let g: Vec<f32> = self.data.iter().map(|c| c.y).collect();
+                let b: Vec<f32> = self.data.iter().map(|c| c.z).collect();
+                let channels = [("R", &r[..]), ("G", &g), ("B", &b), ("A", &self.alpha)];
+
+                let writer = BufWriter::new(File::create(path)?);
+                crate::io::exr::write(writer, self.width, self.height, &channels)
+            }
+            _ => Err(unsupported(path)),
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn get_pixel(&self, x: usize, y: usize) -> Color {
+        assert!(y < self.height);
+        assert!(x < self.width);
+        self.data[y * self.width + x]
+    }
+
+    pub fn set_pixel(&mut self, x: usize, y: usize, color: Color) {
+        assert!(y < self.height);
+        assert!(x < self.width);
+        self.data[y * self.width + x] = color;
+    }
+
+    pub fn get_alpha(&self, x: usize, y: usize) -> f32 {
+        assert!(y < self.height);
+        assert!(x < self.width);
+        self.alpha[y * self.width + x]
+    }
+
+    pub fn set_alpha(&mut self, x: usize, y: usize, alpha: f32) {
+        assert!(y < self.height);
+        assert!(x < self.width);
+        self.alpha[y * self.width + x] = alpha;
+    }
+
+    /// Copies `other` into this image, with its top left corner at `(x, y)`.
+    pub fn blit(&mut self, other: &HdrImage, x: usize, y: usize) {
+        for j in 0..other.height {
+            for i in 0..other.width {
+                self.set_pixel(x + i, y + j, other.get_pixel(i, j));
+                self.set_alpha(x + i, y + j, other.get_alpha(i, j));
+            }
+        }
+    }
+
+    /// Tonemaps the image and quantizes it to 8 bits per channel for display.
+    pub fn to_image(&self, settings: &OutputSettings) -> Image {
+        let mut image = Image::new(self.width, self.height);
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let color = settings.apply(self.get_pixel(x, y));
+                image.set_pixel(x, y, Pixel::from_color(color));
+            }
+        }
+        image
+    }
+}
+
+fn extension(path: &Path) -> Option<String> {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_ascii_lowercase())
+}
+
+fn unsupported(path: &Path) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Unsupported,
+        format!("unsupported image format: {}", path.display()),
+    )
 }
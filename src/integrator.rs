@@ -0,0 +1,419 @@
+//! Light transport algorithms, which estimate the light arriving along camera rays.
+
+mod ambient_occlusion;
+mod bidirectional;
+mod debug;
+mod metropolis;
+mod path;
+mod photon;
+mod whitted;
+
+pub use ambient_occlusion::AmbientOcclusion;
+pub use bidirectional::Bidirectional;
+pub use debug::{DebugIntegrator, DebugView};
+pub use metropolis::Metropolis;
+pub use path::PathTracer;
+pub use photon::PhotonMapper;
+pub use whitted::Whitted;
+
+use super::guiding::{Guide, Guided};
+use super::material::Lobe;
+use super::object::SHADOW_EPSILON;
+use super::{Color, HitRecord, Ray, RenderOptions, Sampler, Scene, Vec3};
+use std::f32::consts::PI;
+use std::ops::Range;
+use std::sync::Arc;
+use std::thread;
+
+/// Estimates the light arriving at the camera along a ray, once per sample.
+pub trait Integrator: Send + Sync {
+    /// Light arriving along `ray`, whose first hit in the scene (found by the
+    /// render loop, within the clipping range) is `hit`.
+    ///
+    /// Light the sample finds reaching the camera through other points of the
+    /// image is added to `splats`.
+    fn radiance(
+        &self,
+        scene: &Scene,
+        ray: Ray,
+        hit: Option<HitRecord>,
+        sampler: &mut dyn Sampler,
+        splats: &mut Vec<Splat>,
+    ) -> Contribution;
+}
+
+/// The integrators that can be selected in `RenderOptions`.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum IntegratorKind {
+    /// Unidirectional path tracing with light sampling, up to `RenderOptions::bounces`.
+    #[default]
+    PathTracer,
+    /// White where surfaces are unoccluded within `distance`, darker in creases.
+    AmbientOcclusion { distance: f32 },
+    /// Only light reaching the first hit directly, from the background or emissive objects.
+    DirectLighting,
+    /// Follows mirror and glass reflections and refractions up to
+    /// `RenderOptions::bounces`, with direct lighting on diffuse surfaces.
+    Whitted,
+    /// Bidirectional path tracing, connecting paths from the camera and from
+    /// emissive objects, up to `RenderOptions::bounces`.
+    Bidirectional,
+    /// Path tracing with caustics from `photons` photons traced from emissive
+    /// objects, blurred over `radius`. Progressive renders shrink the radius
+    /// with each pass.
+    PhotonMapping { photons: usize, radius: f32 },
+    /// Metropolis light transport over bidirectional paths, for scenes lit
+    /// through narrow openings. Each sample runs a chain of `mutations`
+    /// mutations, starting from one of `bootstrap` paths traced up front.
+    Metropolis { bootstrap: usize, mutations: usize },
+    /// Shows a property of the first hit instead of its lighting.
+    Debug(DebugView),
+}
+
+impl IntegratorKind {
+    /// Creates the integrator for rendering `scene` with `options`.
+    pub fn build(self, scene: &Scene, options: RenderOptions) -> Box<dyn Integrator> {
+        self.build_guided(scene, options, None)
+    }
+
+    /// Like `build`, with path tracing guided by `guide`, and recording the
+    /// light its rays find into it.
+    pub(crate) fn build_guided(
+        self,
+        scene: &Scene,
+        options: RenderOptions,
+        guide: Option<Arc<Guide>>,
+    ) -> Box<dyn Integrator> {
+        let t_max = options.clip_end;
+        match self {
+            IntegratorKind::PathTracer => {
+                let path_tracer = PathTracer::new(options.bounces, t_max);
+                match guide {
+                    Some(guide) => Box::new(path_tracer.with_guide(guide)),
+                    None => Box::new(path_tracer),
+                }
+            }
+            IntegratorKind::AmbientOcclusion { distance } => {
+                Box::new(AmbientOcclusion::new(distance))
+            }
+            IntegratorKind::DirectLighting => {
+                Box::new(PathTracer::new(options.bounces.min(2), t_max))
+            }
+            IntegratorKind::Whitted => Box::new(Whitted::new(options.bounces, t_max)),
+            IntegratorKind::Bidirectional => Box::new(Bidirectional::new(scene, options)),
+            IntegratorKind::PhotonMapping { photons, radius } => {
+                Box::new(PhotonMapper::new(scene, options, photons, radius))
+            }
+            IntegratorKind::Metropolis {
+                bootstrap,
+                mutations,
+            } => Box::new(Metropolis::new(scene, options, bootstrap, mutations)),
+            IntegratorKind::Debug(view) => Box::new(DebugIntegrator::new(view, scene)),
+        }
+    }
+
+    /// Whether the light of each sample reaches its own pixel, which adaptive
+    /// sampling needs to measure the pixel's error. Metropolis light transport
+    /// splats all of its light elsewhere, so `RenderOptions::adaptive` is
+    /// ignored for it.
+    pub(crate) fn supports_adaptive(self) -> bool {
+        !matches!(self, IntegratorKind::Metropolis { .. })
+    }
+
+    /// The integrator for the `pass`th pass of a progressive render, counting from 0.
+    ///
+    /// Photon mapping shrinks its radius so that the blur fades as passes are
+    /// averaged, with the area of the radius shrinking by `(i + α) / (i + 1)`
+    /// after pass `i`, for `α` of 2/3 (Knaus and Zwicker, "Progressive Photon
+    /// Mapping: A Probabilistic Approach").
+    pub fn for_pass(self, pass: usize) -> Self {
+        const ALPHA: f32 = 2.0 / 3.0;
+        match self {
+            IntegratorKind::PhotonMapping { photons, radius } => {
+                let area: f32 = (1..=pass)
+                    .map(|i| (i as f32 + ALPHA) / (i as f32 + 1.0))
+                    .product();
+                IntegratorKind::PhotonMapping {
+                    photons,
+                    radius: radius * area.sqrt(),
+                }
+            }
+            kind => kind,
+        }
+    }
+}
+
+/// Light arriving along a ray, split by how many bounces it took after leaving
+/// the first surface: light emitted by the surface (or the background) itself,
+/// light from a single bounce, and light from more bounces.
+///
+/// Integrators that don't simulate lighting return everything as `emitted`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Contribution {
+    pub emitted: Color,
+    pub direct: Color,
+    pub indirect: Color,
+}
+
+impl Contribution {
+    pub fn emitted(color: Color) -> Self {
+        Self {
+            emitted: color,
+            ..Default::default()
+        }
+    }
+
+    pub fn total(&self) -> Color {
+        self.emitted + self.direct + self.indirect
+    }
+
+    pub fn scaled(&self, factor: f32) -> Self {
+        Self {
+            emitted: self.emitted * factor,
+            direct: self.direct * factor,
+            indirect: self.indirect * factor,
+        }
+    }
+
+    /// Adds light that was scattered `bounces` times on its way to the camera.
+    pub fn add(&mut self, bounces: usize, color: Color) {
+        match bounces {
+            0 => self.emitted = self.emitted + color,
+            1 => self.direct = self.direct + color,
+            _ => self.indirect = self.indirect + color,
+        }
+    }
+}
+
+/// Light reaching the camera through a point of the image other than the
+/// sample's own, such as light traced from emissive objects.
+#[derive(Debug, Clone, Copy)]
+pub struct Splat {
+    /// Position on the image, in pixels.
+    pub x: f32,
+    pub y: f32,
+    /// Scattering at the surface the camera sees, which decides the lighting
+    /// passes the splat belongs to, or `None` where it sees the background.
+    pub lobe: Option<Lobe>,
+    pub contribution: Contribution,
+}
+
+/// The emissive objects of a scene, where light subpaths start.
+struct Emitters {
+    /// Indices of the objects, with the sum of their areas and those before them.
+    objects: Vec<(usize, f32)>,
+    area: f32,
+}
+
+impl Emitters {
+    fn new(scene: &Scene) -> Self {
+        let mut objects = Vec::new();
+        let mut area = 0.0;
+        for (index, object) in scene.objects().iter().enumerate() {
+            let emissive = object.materials().iter().any(|m| m.is_emissive());
+            if emissive && object.area() > 0.0 {
+                area += object.area();
+                objects.push((index, area));
+            }
+        }
+        Self { objects, area }
+    }
+
+    /// Picks a point uniformly over the area of all emitters, returning it
+    /// along with its density with respect to area.
+    fn sample<'a>(
+        &self,
+        scene: &'a Scene,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(HitRecord<'a>, f32)> {
+        let target = sampler.next_1d() * self.area;
+        let index = self.objects.partition_point(|&(_, area)| area <= target);
+        let &(object, _) = self.objects.get(index).or(self.objects.last())?;
+        let record = scene.objects()[object].sample_surface(sampler)?;
+        Some((HitRecord { object, ..record }, 1.0 / self.area))
+    }
+
+    /// The density with which `sample` picks the point of `record`, which is
+    /// 0 on objects it never picks, even if they are emissive.
+    fn pdf(&self, record: &HitRecord) -> f32 {
+        let sampled = self
+            .objects
+            .binary_search_by_key(&record.object, |&(index, _)| index)
+            .is_ok();
+        if sampled && record.material.is_emissive() {
+            1.0 / self.area
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Light emitted from the surface of `record` towards `direction`.
+fn emission(record: &HitRecord, direction: Vec3) -> Color {
+    let ray = Ray::new(record.point + direction, -direction);
+    record.material.emit(ray, record)
+}
+
+/// Picks a direction for light to leave an emissive surface, which emits from
+/// both of its sides, returning it with its density with respect to solid angle.
+fn sample_emission(record: &HitRecord, sampler: &mut dyn Sampler) -> (Vec3, f32) {
+    let side = if sampler.next_1d() < 0.5 { 1.0 } else { -1.0 };
+    let normal = record.normal * side;
+    let mut direction = normal + Vec3::random_unit_vector(sampler);
+    if direction.near_zero() {
+        direction = normal;
+    }
+    let direction = direction.unit();
+    (direction, emission_pdf(record.normal, direction))
+}
+
+/// The density with which `sample_emission` picks `direction`.
+fn emission_pdf(normal: Vec3, direction: Vec3) -> f32 {
+    normal.dot(&direction.unit()).abs() / (2.0 * PI)
+}
+
+/// Light arriving from the background along a ray that escaped the scene.
+///
+/// `scatter_pdf` is the pdf with which the previous bounce picked `ray`, if
+/// that bounce also sampled the background directly with `sample_background`.
+fn miss(scene: &Scene, ray: Ray, scatter_pdf: Option<f32>) -> Color {
+    let background = scene.background();
+    let radiance = background.radiance(ray.direction);
+    let light_pdf = background.pdf(ray.direction);
+    match scatter_pdf {
+        Some(pdf) if light_pdf > 0.0 => radiance * power_heuristic(pdf, light_pdf),
+        _ => radiance,
+    }
+}
+
+/// Estimates light arriving directly from the background at a hit, weighted by multiple importance sampling.
+///
+/// `guided` is the path guiding that scattered rays at the hit are sampled
+/// with, if any.
+fn sample_background(
+    scene: &Scene,
+    ray: Ray,
+    record: &HitRecord,
+    guided: Option<&Guided>,
+    sampler: &mut dyn Sampler,
+) -> Color {
+    let black = Color::new(0.0, 0.0, 0.0);
+
+    let Some((direction, radiance, light_pdf)) = scene.background().sample(sampler) else {
+        return black;
+    };
+    let Some((f, scatter_pdf)) = record.material.eval(ray, record, direction) else {
+        return black;
+    };
+    let scatter_pdf = guided.map_or(scatter_pdf, |guided| guided.pdf(scatter_pdf, direction));
+
+    if light_pdf <= 0.0 || f.near_zero() {
+        return black;
+    }
+
+    let shadow_ray = record.spawn_ray(direction);
+    if scene.hit(shadow_ray, 0.0, f32::INFINITY).is_some() {
+        return black;
+    }
+
+    f * radiance * (power_heuristic(light_pdf, scatter_pdf) / light_pdf)
+}
+
+/// Light emitted by an object that a scattered ray hit, weighted against
+/// `sample_emitter` by multiple importance sampling.
+///
+/// `scatter_pdf` is the pdf with which the previous bounce picked `ray`, if
+/// that bounce also sampled the emitters directly.
+fn hit_emitter(
+    emitters: &Emitters,
+    ray: Ray,
+    record: &HitRecord,
+    scatter_pdf: Option<f32>,
+) -> Color {
+    let radiance = record.material.emit(ray, record);
+    let Some(scatter_pdf) = scatter_pdf else {
+        return radiance;
+    };
+
+    let distance = record.t * ray.direction.len();
+    let cosine = record.normal.dot(&ray.direction.unit()).abs();
+    let light_pdf = emitters.pdf(record) * distance * distance / cosine;
+    if light_pdf > 0.0 && light_pdf.is_finite() {
+        radiance * power_heuristic(scatter_pdf, light_pdf)
+    } else {
+        radiance
+    }
+}
+
+/// Estimates light arriving directly from a random point on an emissive
+/// object at a hit, weighted by multiple importance sampling.
+fn sample_emitter(
+    scene: &Scene,
+    emitters: &Emitters,
+    ray: Ray,
+    record: &HitRecord,
+    sampler: &mut dyn Sampler,
+) -> Color {
+    let black = Color::new(0.0, 0.0, 0.0);
+
+    let Some((light, area_pdf)) = emitters.sample(scene, sampler) else {
+        return black;
+    };
+    let offset = light.point - record.point;
+    let distance = offset.len();
+    let direction = offset / distance;
+    let cosine = light.normal.dot(&direction).abs();
+    if cosine <= 0.0 || distance == 0.0 {
+        return black;
+    }
+
+    let Some((f, scatter_pdf)) = record.material.eval(ray, record, direction) else {
+        return black;
+    };
+    if f.near_zero() {
+        return black;
+    }
+
+    let shadow_ray = record.spawn_ray_to(light.offset_point(-offset));
+    if scene.hit(shadow_ray, 0.0, 1.0 - SHADOW_EPSILON).is_some() {
+        return black;
+    }
+
+    let light_pdf = area_pdf * distance * distance / cosine;
+    let radiance = emission(&light, -direction);
+    f * radiance * (power_heuristic(light_pdf, scatter_pdf) / light_pdf)
+}
+
+/// Calls `f` on ranges splitting up `0..count` on up to `threads` threads,
+/// returning the results in order.
+fn parallel<T: Send>(
+    threads: usize,
+    count: usize,
+    f: impl Fn(Range<usize>) -> Vec<T> + Sync,
+) -> Vec<T> {
+    if count == 0 {
+        return Vec::new();
+    }
+
+    let threads = threads.clamp(1, count);
+    let chunk = count.div_ceil(threads);
+    let f = &f;
+    thread::scope(|s| {
+        let handles: Vec<_> = (0..threads)
+            .map(|thread| s.spawn(move || f(thread * chunk..((thread + 1) * chunk).min(count))))
+            .collect();
+        handles
+            .into_iter()
+            .flat_map(|h| h.join().unwrap())
+            .collect()
+    })
+}
+
+fn power_heuristic(pdf: f32, other_pdf: f32) -> f32 {
+    let (a, b) = (pdf * pdf, other_pdf * other_pdf);
+    if a + b > 0.0 {
+        a / (a + b)
+    } else {
+        0.0
+    }
+}
@@ -0,0 +1,41 @@
+use super::{Contribution, Integrator, Splat};
+use crate::{Color, HitRecord, Ray, Sampler, Scene, Vec3};
+
+/// The fraction of the hemisphere above a hit, weighted by cosine, that is
+/// not blocked by other surfaces within a distance.
+pub struct AmbientOcclusion {
+    distance: f32,
+}
+
+impl AmbientOcclusion {
+    pub fn new(distance: f32) -> Self {
+        Self { distance }
+    }
+}
+
+impl Integrator for AmbientOcclusion {
+    fn radiance(
+        &self,
+        scene: &Scene,
+        _ray: Ray,
+        hit: Option<HitRecord>,
+        sampler: &mut dyn Sampler,
+        _splats: &mut Vec<Splat>,
+    ) -> Contribution {
+        let Some(record) = hit else {
+            return Contribution::default();
+        };
+
+        let mut direction = record.normal + Vec3::random_unit_vector(sampler);
+        if direction.near_zero() {
+            direction = record.normal;
+        }
+
+        let occlusion_ray = record.spawn_ray(direction.unit());
+        let visible = match scene.hit(occlusion_ray, 0.0, self.distance) {
+            Some(_) => 0.0,
+            None => 1.0,
+        };
+        Contribution::emitted(Color::new(1.0, 1.0, 1.0) * visible)
+    }
+}
@@ -0,0 +1,531 @@
+use super::{
+    emission, emission_pdf, miss, sample_background, sample_emission, Contribution, Emitters,
+    Integrator, Splat,
+};
+use crate::camera::Camera;
+use crate::object::SHADOW_EPSILON;
+use crate::{Color, HitRecord, Point, Ray, RenderOptions, Sampler, Scene, Vec3};
+
+/// The sampler streams that the camera subpath, the light subpath and the
+/// connections between them take their values from.
+pub(super) const CAMERA_STREAM: usize = 0;
+pub(super) const LIGHT_STREAM: usize = 1;
+pub(super) const CONNECTION_STREAM: usize = 2;
+pub(super) const STREAMS: usize = 3;
+
+/// Bidirectional path tracing, following Veach, "Robust Monte Carlo Methods
+/// for Light Transport Simulation", chapter 10.
+///
+/// Each sample traces a subpath from the camera and another from a point on
+/// an emissive object, then connects every vertex of one to every vertex of
+/// the other. The paths found this way are weighted by multiple importance
+/// sampling, so each is mostly counted by the strategies likely to find it.
+/// Paths connected straight to the camera are splatted to wherever they land
+/// on the image.
+///
+/// Light subpaths only start on emissive objects; light from the background
+/// is found by the camera subpath, sampling it at every bounce like `PathTracer`.
+pub struct Bidirectional {
+    bounces: usize,
+    clip_start: f32,
+    t_max: f32,
+    width: usize,
+    height: usize,
+    emitters: Emitters,
+}
+
+impl Bidirectional {
+    pub fn new(scene: &Scene, options: RenderOptions) -> Self {
+        Self {
+            bounces: options.bounces,
+            clip_start: options.clip_start,
+            t_max: options.clip_end,
+            width: options.width,
+            height: options.height,
+            emitters: Emitters::new(scene),
+        }
+    }
+
+    /// Traces the camera's subpath, adding light from the background to `background`.
+    fn camera_subpath<'a>(
+        &self,
+        scene: &'a Scene,
+        ray: Ray,
+        hit: Option<HitRecord<'a>>,
+        sampler: &mut dyn Sampler,
+        background: &mut Contribution,
+    ) -> Vec<Vertex<'a>> {
+        let camera = scene.camera();
+        let mut path = vec![Vertex {
+            kind: Kind::Camera,
+            point: ray.origin,
+            normal: camera.forward(),
+            beta: Color::new(1.0, 1.0, 1.0),
+            delta: false,
+            pdf_fwd: 0.0,
+            pdf_rev: 0.0,
+        }];
+
+        let pdf = camera.pdf_direction(ray.origin, ray.direction);
+        let beta = Color::new(1.0, 1.0, 1.0);
+        let walk = Walk {
+            ray,
+            hit,
+            beta,
+            pdf,
+        };
+        self.random_walk(
+            scene,
+            walk,
+            self.bounces + 1,
+            sampler,
+            &mut path,
+            Some(background),
+        );
+        path
+    }
+
+    fn light_subpath<'a>(&self, scene: &'a Scene, sampler: &mut dyn Sampler) -> Vec<Vertex<'a>> {
+        let mut path = Vec::new();
+        if self.bounces == 0 {
+            return path;
+        }
+
+        let Some((record, pdf)) = self.emitters.sample(scene, sampler) else {
+            return path;
+        };
+        let light = Vertex {
+            kind: Kind::Light(record),
+            point: record.point,
+            normal: record.normal,
+            beta: Color::new(1.0, 1.0, 1.0) / pdf,
+            delta: false,
+            pdf_fwd: pdf,
+            pdf_rev: 0.0,
+        };
+        path.push(light);
+
+        let (direction, pdf) = sample_emission(&record, sampler);
+        let beta = light.beta * light.f(direction) / pdf;
+        if pdf == 0.0 || beta.near_zero() {
+            return path;
+        }
+
+        let ray = record.spawn_ray(direction);
+        let hit = scene.hit(ray, 0.0, self.t_max);
+        let walk = Walk {
+            ray,
+            hit,
+            beta,
+            pdf,
+        };
+        self.random_walk(scene, walk, self.bounces, sampler, &mut path, None);
+        path
+    }
+
+    /// Extends `path` by following scattered rays until it has `length`
+    /// vertices, or a ray escapes or is absorbed.
+    ///
+    /// Camera subpaths pass `background`, which collects light from the
+    /// background, both where the path escapes and by sampling it at each
+    /// vertex. Other subpaths carry light from emissive objects.
+    fn random_walk<'a>(
+        &self,
+        scene: &'a Scene,
+        walk: Walk<'a>,
+        length: usize,
+        sampler: &mut dyn Sampler,
+        path: &mut Vec<Vertex<'a>>,
+        mut background: Option<&mut Contribution>,
+    ) {
+        let Walk {
+            mut ray,
+            mut hit,
+            mut beta,
+            mut pdf,
+        } = walk;
+        let mut scatter_pdf = None;
+
+        while path.len() < length {
+            let bounces = path.len() - 1;
+            let Some(mut record) = hit else {
+                if let Some(background) = background {
+                    background.add(bounces, beta * miss(scene, ray, scatter_pdf));
+                }
+                break;
+            };
+            record.compute_footprint(&ray);
+
+            let mut vertex = Vertex::surface(record, ray, beta);
+            vertex.pdf_fwd = path[path.len() - 1].convert(pdf, &vertex);
+            path.push(vertex);
+            if path.len() == length {
+                break;
+            }
+
+            if let Some(background) = background.as_deref_mut() {
+                let light = sample_background(scene, ray, &record, None, sampler);
+                background.add(bounces + 1, beta * light);
+            }
+
+            let material = record.material;
+            let Some((scattered, weight)) = material.scatter(ray, record, sampler) else {
+                break;
+            };
+
+            // Scattering that can't be evaluated is a delta distribution, whose
+            // densities are left at zero.
+            let eval = material.eval(ray, &record, scattered.direction);
+            let reverse = Ray::new(record.point + scattered.direction, -scattered.direction);
+            let pdf_rev = match eval {
+                Some(_) => material
+                    .eval(reverse, &record, -ray.direction)
+                    .map_or(0.0, |(_, pdf)| pdf),
+                None => 0.0,
+            };
+
+            let n = path.len();
+            path[n - 2].pdf_rev = path[n - 1].convert(pdf_rev, &path[n - 2]);
+
+            scatter_pdf = eval.map(|(_, pdf)| pdf);
+            pdf = scatter_pdf.unwrap_or(0.0);
+            beta = beta * weight;
+            if background.is_none() {
+                beta = beta * material.light_scale(ray, &record, scattered.direction);
+            }
+            ray = scattered;
+            hit = scene.hit(ray, 0.0, self.t_max);
+        }
+    }
+
+    /// Light along the path made of the first `t` vertices of `camera` and
+    /// the first `s` vertices of `light`, for `t` of at least 2.
+    fn connect(
+        &self,
+        scene: &Scene,
+        camera: &[Vertex],
+        light: &[Vertex],
+        s: usize,
+        t: usize,
+    ) -> Color {
+        let black = Color::new(0.0, 0.0, 0.0);
+        let pt = &camera[t - 1];
+
+        let color = if s == 0 {
+            match pt.kind {
+                Kind::Surface(record, ray) => pt.beta * record.material.emit(ray, &record),
+                _ => black,
+            }
+        } else {
+            let qs = &light[s - 1];
+            if qs.delta || pt.delta {
+                return black;
+            }
+
+            let d = pt.point - qs.point;
+            let distance = d.len();
+            let direction = d / distance;
+            let color =
+                qs.beta * qs.f(direction) * pt.f(-direction) * pt.beta / (distance * distance);
+            if color.near_zero() || !self.visible(scene, qs, pt) {
+                return black;
+            }
+            color
+        };
+
+        if color.near_zero() {
+            return black;
+        }
+        color * self.mis_weight(scene.camera(), camera, light, None, s, t)
+    }
+
+    /// Connects the last of the first `s` vertices of `light` straight to the
+    /// camera, for `s` of at least 2.
+    fn connect_to_camera(
+        &self,
+        scene: &Scene,
+        camera: &[Vertex],
+        light: &[Vertex],
+        s: usize,
+        sampler: &mut dyn Sampler,
+    ) -> Option<Splat> {
+        let qs = &light[s - 1];
+        let Kind::Surface(record, _) = qs.kind else {
+            return None;
+        };
+        if qs.delta {
+            return None;
+        }
+
+        let lens = scene.camera().sample_lens(qs.point, sampler)?;
+        let direction = (lens.point - qs.point).unit();
+        let beta = Color::new(1.0, 1.0, 1.0) * (lens.importance / lens.pdf);
+        let sampled = Vertex {
+            kind: Kind::Camera,
+            point: lens.point,
+            normal: scene.camera().forward(),
+            beta,
+            delta: false,
+            pdf_fwd: 0.0,
+            pdf_rev: 0.0,
+        };
+        let color = qs.beta * qs.f(direction) * beta;
+        if color.near_zero() || !self.visible(scene, qs, &sampled) {
+            return None;
+        }
+
+        let weight = self.mis_weight(scene.camera(), camera, light, Some(sampled), s, 1);
+
+        let mut contribution = Contribution::default();
+        contribution.add(s - 1, color * weight);
+        Some(Splat {
+            x: lens.frac_x * self.width as f32,
+            y: lens.frac_y * self.height as f32,
+            lobe: Some(record.material.lobe()),
+            contribution,
+        })
+    }
+
+    /// Whether nothing lies between two vertices. Near the camera, only what
+    /// camera rays would hit past `clip_start` counts.
+    fn visible(&self, scene: &Scene, from: &Vertex, to: &Vertex) -> bool {
+        let origin = from.offset_towards(to.point);
+        let ray = Ray::new(origin, to.offset_towards(origin) - origin);
+        let mut t_max = 1.0 - SHADOW_EPSILON;
+        if matches!(to.kind, Kind::Camera) {
+            t_max -= self.clip_start / ray.direction.len();
+        }
+        scene.hit(ray, 0.0, t_max).is_none()
+    }
+
+    /// The power heuristic weight of connecting the first `s` vertices of
+    /// `light` to the first `t` of `camera`, against every other way of
+    /// splitting the same path between the two subpaths.
+    ///
+    /// For `t` of 1, `sampled` is the lens point the path was connected to.
+    fn mis_weight(
+        &self,
+        model: &Camera,
+        camera: &[Vertex],
+        light: &[Vertex],
+        sampled: Option<Vertex>,
+        s: usize,
+        t: usize,
+    ) -> f32 {
+        // A light seen directly can only be found by the camera subpath.
+        if s + t == 2 {
+            return 1.0;
+        }
+
+        let mut camera = camera[..t].to_vec();
+        let mut light = light[..s].to_vec();
+        if let Some(sampled) = sampled {
+            camera[0] = sampled;
+        }
+
+        // The connection gives the vertices at either end of it, and their
+        // predecessors, new densities of being sampled from the other side.
+        let pt_rev = match s {
+            0 => match camera[t - 1].kind {
+                Kind::Surface(record, _) => self.emitters.pdf(&record),
+                _ => 0.0,
+            },
+            _ => light[s - 1].pdf(model, s.checked_sub(2).map(|i| &light[i]), &camera[t - 1]),
+        };
+        // Paths ending on surfaces light subpaths can't start from are only
+        // found by the camera subpath.
+        if s == 0 && pt_rev == 0.0 {
+            return 1.0;
+        }
+        let pt_minus_rev = (t > 1).then(|| match s {
+            0 => camera[t - 1].pdf_light(&camera[t - 2]),
+            _ => camera[t - 1].pdf(model, Some(&light[s - 1]), &camera[t - 2]),
+        });
+        let qs_rev = (s > 0).then(|| {
+            let prev = t.checked_sub(2).map(|i| &camera[i]);
+            camera[t - 1].pdf(model, prev, &light[s - 1])
+        });
+        let qs_minus_rev =
+            (s > 1).then(|| light[s - 1].pdf(model, Some(&camera[t - 1]), &light[s - 2]));
+
+        camera[t - 1].pdf_rev = pt_rev;
+        camera[t - 1].delta = false;
+        if let Some(pdf) = pt_minus_rev {
+            camera[t - 2].pdf_rev = pdf;
+        }
+        if let Some(pdf) = qs_rev {
+            light[s - 1].pdf_rev = pdf;
+            light[s - 1].delta = false;
+        }
+        if let Some(pdf) = qs_minus_rev {
+            light[s - 2].pdf_rev = pdf;
+        }
+
+        // Delta distributions have zero densities, which cancel out between
+        // the strategies that share them.
+        let remap = |pdf: f32| if pdf != 0.0 { pdf } else { 1.0 };
+
+        let mut sum = 0.0;
+        let mut ratio = 1.0;
+        for i in (1..t).rev() {
+            ratio *= remap(camera[i].pdf_rev) / remap(camera[i].pdf_fwd);
+            if !camera[i].delta && !camera[i - 1].delta {
+                sum += ratio * ratio;
+            }
+        }
+
+        ratio = 1.0;
+        for i in (0..s).rev() {
+            ratio *= remap(light[i].pdf_rev) / remap(light[i].pdf_fwd);
+            let delta_before = i > 0 && light[i - 1].delta;
+            if !light[i].delta && !delta_before {
+                sum += ratio * ratio;
+            }
+        }
+
+        1.0 / (1.0 + sum)
+    }
+}
+
+impl Integrator for Bidirectional {
+    fn radiance(
+        &self,
+        scene: &Scene,
+        ray: Ray,
+        hit: Option<HitRecord>,
+        sampler: &mut dyn Sampler,
+        splats: &mut Vec<Splat>,
+    ) -> Contribution {
+        let mut contribution = Contribution::default();
+        let camera = self.camera_subpath(scene, ray, hit, sampler, &mut contribution);
+        sampler.start_stream(LIGHT_STREAM);
+        let light = self.light_subpath(scene, sampler);
+        sampler.start_stream(CONNECTION_STREAM);
+
+        for t in 1..=camera.len() {
+            for s in 0..=light.len() {
+                // Paths have s + t - 2 bounces, and need at least one vertex besides the camera.
+                if s + t < 2 || s + t > self.bounces + 1 || (s == 1 && t == 1) {
+                    continue;
+                }
+
+                if t == 1 {
+                    splats.extend(self.connect_to_camera(scene, &camera, &light, s, sampler));
+                } else {
+                    let color = self.connect(scene, &camera, &light, s, t);
+                    contribution.add(s + t - 2, color);
+                }
+            }
+        }
+        contribution
+    }
+}
+
+/// A ray leaving the end of a subpath: its first hit, the subpath's
+/// throughput and the density with which the ray was picked, with respect to
+/// solid angle.
+struct Walk<'a> {
+    ray: Ray,
+    hit: Option<HitRecord<'a>>,
+    beta: Color,
+    pdf: f32,
+}
+
+#[derive(Clone, Copy)]
+enum Kind<'a> {
+    /// A point on the lens.
+    Camera,
+    /// A point on an emissive object, starting a light subpath.
+    Light(HitRecord<'a>),
+    /// A surface that was reached by the ray.
+    Surface(HitRecord<'a>, Ray),
+}
+
+#[derive(Clone, Copy)]
+struct Vertex<'a> {
+    kind: Kind<'a>,
+    point: Point,
+    normal: Vec3,
+    /// Throughput of the subpath up to this vertex, divided by its density.
+    beta: Color,
+    /// Whether scattering here can't be evaluated, so the vertex can't be connected to.
+    delta: bool,
+    /// Densities of the vertex, with respect to area, when sampled by its own
+    /// subpath and by the subpath coming from the other end.
+    pdf_fwd: f32,
+    pdf_rev: f32,
+}
+
+impl<'a> Vertex<'a> {
+    fn surface(record: HitRecord<'a>, ray: Ray, beta: Color) -> Self {
+        Self {
+            kind: Kind::Surface(record, ray),
+            point: record.point,
+            normal: record.normal,
+            beta,
+            delta: record.material.eval(ray, &record, record.normal).is_none(),
+            pdf_fwd: 0.0,
+            pdf_rev: 0.0,
+        }
+    }
+
+    /// The vertex's point, moved off its surface towards `point`.
+    fn offset_towards(&self, point: Point) -> Point {
+        match self.kind {
+            Kind::Camera => self.point,
+            Kind::Light(record) | Kind::Surface(record, _) => {
+                record.offset_point(point - self.point)
+            }
+        }
+    }
+
+    /// Scattering towards unit `direction`, or for lights emission, including the cosine term.
+    fn f(&self, direction: Vec3) -> Color {
+        match self.kind {
+            Kind::Camera => Color::new(0.0, 0.0, 0.0),
+            Kind::Light(record) => {
+                emission(&record, direction) * record.normal.dot(&direction).abs()
+            }
+            Kind::Surface(record, ray) => record
+                .material
+                .eval(ray, &record, direction)
+                .map_or(Color::new(0.0, 0.0, 0.0), |(f, _)| f),
+        }
+    }
+
+    /// Converts a density of directions leaving this vertex into a density
+    /// of points on the surface of `next`.
+    fn convert(&self, pdf: f32, next: &Vertex) -> f32 {
+        let d = next.point - self.point;
+        let distance_squared = d.dot(&d);
+        if distance_squared == 0.0 {
+            return 0.0;
+        }
+        pdf * next.normal.dot(&d).abs() / (distance_squared * distance_squared.sqrt())
+    }
+
+    /// The density with which this vertex, having been reached from `prev`,
+    /// samples `next`, with respect to area.
+    fn pdf(&self, camera: &Camera, prev: Option<&Vertex>, next: &Vertex) -> f32 {
+        let direction = (next.point - self.point).unit();
+        let pdf = match self.kind {
+            Kind::Camera => camera.pdf_direction(self.point, direction),
+            Kind::Light(record) => emission_pdf(record.normal, direction),
+            Kind::Surface(record, ray) => {
+                let ray = prev.map_or(ray, |prev| Ray::new(prev.point, self.point - prev.point));
+                record
+                    .material
+                    .eval(ray, &record, direction)
+                    .map_or(0.0, |(_, pdf)| pdf)
+            }
+        };
+        self.convert(pdf, next)
+    }
+
+    /// The density with which light emitted from this vertex reaches `next`,
+    /// with respect to area.
+    fn pdf_light(&self, next: &Vertex) -> f32 {
+        let direction = next.point - self.point;
+        self.convert(emission_pdf(self.normal, direction), next)
+    }
+}
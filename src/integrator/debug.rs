@@ -0,0 +1,91 @@
+use super::{Contribution, Integrator, Splat};
+use crate::rng::mix;
+use crate::scene::MaterialIds;
+use crate::{Color, HitRecord, Ray, Sampler, Scene};
+
+/// A property of the first hit shown by `DebugIntegrator`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DebugView {
+    /// World space normals, mapped from `[-1, 1]` to `[0, 1]`.
+    Normal,
+    /// Texture coordinates in the red and green channels.
+    Uv,
+    /// Distance from the camera along its viewing direction, from black at
+    /// the camera to white at `max_depth` and beyond.
+    Depth { max_depth: f32 },
+    /// Barycentric coordinates of mesh hits, one per channel.
+    Barycentric,
+    /// Triangle edges of meshes in white, within `width` in barycentric
+    /// coordinates, over surfaces shaded by the angle they are seen at.
+    Wireframe { width: f32 },
+    /// A distinct color for each material.
+    MaterialId,
+}
+
+/// Shows a property of the first hit of each camera ray instead of its lighting.
+pub struct DebugIntegrator {
+    view: DebugView,
+    materials: MaterialIds,
+}
+
+impl DebugIntegrator {
+    pub fn new(view: DebugView, scene: &Scene) -> Self {
+        Self {
+            view,
+            materials: MaterialIds::new(scene),
+        }
+    }
+}
+
+impl Integrator for DebugIntegrator {
+    fn radiance(
+        &self,
+        scene: &Scene,
+        ray: Ray,
+        hit: Option<HitRecord>,
+        _sampler: &mut dyn Sampler,
+        _splats: &mut Vec<Splat>,
+    ) -> Contribution {
+        let black = Color::new(0.0, 0.0, 0.0);
+        let white = Color::new(1.0, 1.0, 1.0);
+
+        let Some(record) = hit else {
+            return Contribution::default();
+        };
+
+        let color = match self.view {
+            DebugView::Normal => (record.normal + white) * 0.5,
+            DebugView::Uv => Color::new(record.uv.0, record.uv.1, 0.0),
+            DebugView::Depth { max_depth } => {
+                white * (scene.camera().depth(record.point) / max_depth).min(1.0)
+            }
+            DebugView::Barycentric => match record.barycentric {
+                Some((u, v)) => Color::new(1.0 - u - v, u, v),
+                None => black,
+            },
+            DebugView::Wireframe { width } => {
+                let edge = record
+                    .barycentric
+                    .is_some_and(|(u, v)| u.min(v).min(1.0 - u - v) < width);
+                if edge {
+                    white
+                } else {
+                    let facing = record.normal.dot(&ray.direction.unit()).abs();
+                    white * (0.1 + 0.4 * facing)
+                }
+            }
+            DebugView::MaterialId => match self.materials.id(scene, &record) {
+                Some(index) => false_color(index),
+                None => black,
+            },
+        };
+        Contribution::emitted(color)
+    }
+}
+
+/// A bright, arbitrary color for an index, differing between neighbouring indices.
+fn false_color(index: usize) -> Color {
+    let hash = mix(index as u64);
+    let channel = |shift: u64| 0.2 + 0.8 * ((hash >> shift) & 0xff) as f32 / 255.0;
+    Color::new(channel(0), channel(8), channel(16))
+}
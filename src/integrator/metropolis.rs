@@ -0,0 +1,310 @@
+use super::bidirectional::{CAMERA_STREAM, STREAMS};
+use super::{parallel, Bidirectional, Contribution, Integrator, Splat};
+use crate::distribution::Distribution1D;
+use crate::rng::mix;
+use crate::{HitRecord, Ray, RenderOptions, Rng, Sampler, Scene};
+use std::f32::consts::PI;
+
+/// Chance of a mutation replacing every sample value, rather than moving them slightly.
+const LARGE_STEP_PROBABILITY: f32 = 0.3;
+/// Standard deviation of the small moves, in primary sample space.
+const SIGMA: f32 = 0.01;
+
+/// Primary sample space Metropolis light transport (Kelemen et al., "A Simple
+/// and Robust Mutation Strategy for the Metropolis Light Transport Algorithm").
+///
+/// Paths are generated by bidirectional path tracing from sample values that
+/// a Markov chain mutates, favouring values that give brighter paths. Once a
+/// bright path is found, nearby paths are explored by small changes to its
+/// values, so light through narrow openings is found far more often than by
+/// sampling every path independently.
+///
+/// Each sample runs a short chain of `mutations` mutations, starting from one
+/// of `bootstrap` paths traced before rendering, picked by brightness. Their
+/// mean brightness normalises the image, and every state of the chain is
+/// splatted to wherever it lands on the image.
+pub struct Metropolis {
+    paths: Bidirectional,
+    mutations: usize,
+    seed: u64,
+    width: usize,
+    height: usize,
+    crop_region: ((usize, usize), (usize, usize)),
+    differential_scale: f32,
+    t_min: f32,
+    t_max: f32,
+    /// Brightness of the bootstrap paths.
+    bootstrap: Distribution1D,
+}
+
+impl Metropolis {
+    pub fn new(scene: &Scene, options: RenderOptions, bootstrap: usize, mutations: usize) -> Self {
+        let samples = (options.samples * mutations).max(1);
+        let mut metropolis = Self {
+            paths: Bidirectional::new(scene, options),
+            mutations,
+            seed: mix(options.seed),
+            width: options.width,
+            height: options.height,
+            crop_region: options.crop_region,
+            differential_scale: f32::max(0.125, 1.0 / (samples as f32).sqrt()),
+            t_min: options.clip_start,
+            t_max: options.clip_end,
+            bootstrap: Distribution1D::new(Vec::new()),
+        };
+
+        let brightness = parallel(options.thread_count(), bootstrap, |range| {
+            let metropolis = &metropolis;
+            range
+                .map(|index| {
+                    let mut sampler = metropolis.bootstrap_sampler(index);
+                    metropolis.evaluate(scene, &mut sampler).brightness
+                })
+                .collect()
+        });
+        metropolis.bootstrap = Distribution1D::new(brightness);
+        metropolis
+    }
+
+    /// The sampler starting at the values of the `index`th bootstrap path.
+    fn bootstrap_sampler(&self, index: usize) -> MetropolisSampler {
+        MetropolisSampler::new(Rng::new(self.seed, index as u64))
+    }
+
+    /// Traces the path given by the current values of `sampler`.
+    fn evaluate(&self, scene: &Scene, sampler: &mut MetropolisSampler) -> State {
+        sampler.start_sample(0, 0, 0);
+        sampler.start_stream(CAMERA_STREAM);
+
+        // Paths only start in the crop region, so they are found that much
+        // more often than the scale of splats expects.
+        let ((x_start, x_end), (y_start, y_end)) = self.crop_region;
+        let (crop_width, crop_height) = (x_end - x_start, y_end - y_start);
+        let crop_area = (crop_width * crop_height) as f32 / (self.width * self.height) as f32;
+
+        let (u, v) = sampler.next_2d();
+        let x = x_start as f32 + u * crop_width as f32;
+        let y = y_start as f32 + v * crop_height as f32;
+        let (frac_x, frac_y) = (x / self.width as f32, y / self.height as f32);
+        let (dx, dy) = (1.0 / self.width as f32, 1.0 / self.height as f32);
+
+        let mut ray = scene
+            .camera()
+            .ray_differential(frac_x, frac_y, dx, dy, sampler);
+        ray.scale_differential(self.differential_scale);
+        let hit = scene.hit(ray, self.t_min, self.t_max);
+
+        let mut splats = Vec::new();
+        let contribution = self.paths.radiance(scene, ray, hit, sampler, &mut splats);
+        splats.push(Splat {
+            x,
+            y,
+            lobe: hit.map(|record| record.material.lobe()),
+            contribution: contribution.scaled(crop_area),
+        });
+
+        let brightness = splats
+            .iter()
+            .map(|splat| splat.contribution.total().luminance())
+            .sum();
+        State { splats, brightness }
+    }
+}
+
+impl Integrator for Metropolis {
+    fn radiance(
+        &self,
+        scene: &Scene,
+        _ray: Ray,
+        _hit: Option<HitRecord>,
+        sampler: &mut dyn Sampler,
+        splats: &mut Vec<Splat>,
+    ) -> Contribution {
+        let normalization = self.bootstrap.integral();
+        if self.mutations == 0 || normalization <= 0.0 {
+            return Contribution::default();
+        }
+
+        let (_, _, index) = self.bootstrap.sample(sampler.next_1d());
+        let mut chain = self.bootstrap_sampler(index);
+        let mut current = self.evaluate(scene, &mut chain);
+
+        // Chains starting from the same bootstrap path go separate ways.
+        let (a, b) = sampler.next_2d();
+        let stream = ((a * (1 << 24) as f32) as u64) << 24 | (b * (1 << 24) as f32) as u64;
+        chain.rng = Rng::new(mix(self.seed), stream);
+
+        let scale = normalization / self.mutations as f32;
+        for _ in 0..self.mutations {
+            chain.start_iteration();
+            let proposed = self.evaluate(scene, &mut chain);
+            let accept = if current.brightness > 0.0 {
+                (proposed.brightness / current.brightness).min(1.0)
+            } else {
+                1.0
+            };
+
+            // Both states are splatted, weighted by the chance of the chain
+            // moving to each, rather than only the state it moves to.
+            proposed.splat(splats, scale * accept);
+            current.splat(splats, scale * (1.0 - accept));
+
+            if chain.rng.uniform() < accept {
+                current = proposed;
+                chain.accept();
+            } else {
+                chain.reject();
+            }
+        }
+        Contribution::default()
+    }
+}
+
+/// The light a path brings to the image.
+struct State {
+    splats: Vec<Splat>,
+    /// Total luminance of the splats, which the chain's states are distributed by.
+    brightness: f32,
+}
+
+impl State {
+    fn splat(&self, splats: &mut Vec<Splat>, weight: f32) {
+        if weight <= 0.0 || self.brightness <= 0.0 {
+            return;
+        }
+        for splat in &self.splats {
+            splats.push(Splat {
+                contribution: splat.contribution.scaled(weight / self.brightness),
+                ..*splat
+            });
+        }
+    }
+}
+
+/// The sample values of a Markov chain's current state.
+///
+/// Each value is mutated when it is first used in an iteration, so values
+/// that the current path doesn't use are left alone. Values skipped over by
+/// mutations are caught up with when they are next used.
+///
+/// The values of the streams are interleaved, so a small step that makes the
+/// camera subpath longer or shorter leaves the light subpath's values in place.
+struct MetropolisSampler {
+    rng: Rng,
+    values: Vec<Value>,
+    stream: usize,
+    /// The next dimension within the current stream.
+    dimension: usize,
+    iteration: usize,
+    large_step: bool,
+    last_large_step: usize,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Value {
+    value: f32,
+    /// The iteration the value was last mutated in.
+    modified: usize,
+    backup: f32,
+    backup_modified: usize,
+}
+
+impl MetropolisSampler {
+    /// Starts at values drawn from `rng`.
+    fn new(rng: Rng) -> Self {
+        Self {
+            rng,
+            values: Vec::new(),
+            stream: 0,
+            dimension: 0,
+            iteration: 0,
+            large_step: true,
+            last_large_step: 0,
+        }
+    }
+
+    /// Starts mutating the values for a proposed state.
+    fn start_iteration(&mut self) {
+        self.iteration += 1;
+        self.large_step = self.rng.uniform() < LARGE_STEP_PROBABILITY;
+    }
+
+    /// Keeps the proposed state.
+    fn accept(&mut self) {
+        if self.large_step {
+            self.last_large_step = self.iteration;
+        }
+    }
+
+    /// Restores the values from before the proposed state.
+    fn reject(&mut self) {
+        for value in self.values.iter_mut() {
+            if value.modified == self.iteration {
+                value.value = value.backup;
+                value.modified = value.backup_modified;
+            }
+        }
+        self.iteration -= 1;
+    }
+
+    /// Mutates the value of `dimension` for the current iteration.
+    fn mutate(&mut self, dimension: usize) -> f32 {
+        // Values the chain hasn't used yet are as good as any drawn at the last large step.
+        while dimension >= self.values.len() {
+            let value = Value {
+                value: self.rng.uniform(),
+                modified: self.last_large_step,
+                ..Default::default()
+            };
+            self.values.push(value);
+        }
+        let value = &mut self.values[dimension];
+
+        if value.modified < self.last_large_step {
+            value.value = self.rng.uniform();
+            value.modified = self.last_large_step;
+        }
+
+        value.backup = value.value;
+        value.backup_modified = value.modified;
+        if self.large_step {
+            value.value = self.rng.uniform();
+        } else {
+            // Small steps taken since the value was last used add up.
+            let steps = (self.iteration - value.modified) as f32;
+            let normal = {
+                let (u1, u2) = (1.0 - self.rng.uniform(), self.rng.uniform());
+                (-2.0 * u1.ln()).sqrt() * (2.0 * PI * u2).cos()
+            };
+            value.value += normal * SIGMA * steps.sqrt();
+            value.value -= value.value.floor();
+            if value.value >= 1.0 {
+                value.value = 0.0;
+            }
+        }
+        value.modified = self.iteration;
+        value.value
+    }
+}
+
+impl Sampler for MetropolisSampler {
+    /// Restarts at the first dimension. Every path uses the same values, so
+    /// the pixel and sample are ignored.
+    fn start_sample(&mut self, _x: usize, _y: usize, _index: usize) {
+        self.start_stream(0);
+    }
+
+    fn next_1d(&mut self) -> f32 {
+        self.dimension += 1;
+        self.mutate(self.stream + STREAMS * (self.dimension - 1))
+    }
+
+    fn next_2d(&mut self) -> (f32, f32) {
+        (self.next_1d(), self.next_1d())
+    }
+
+    fn start_stream(&mut self, stream: usize) {
+        self.stream = stream;
+        self.dimension = 0;
+    }
+}
@@ -0,0 +1,139 @@
+use super::{miss, sample_background, Contribution, Integrator, Splat};
+use crate::guiding::Guide;
+use crate::material::Lobe;
+use crate::{Color, HitRecord, Ray, Sampler, Scene};
+use std::sync::Arc;
+
+/// Unidirectional path tracing, sampling the background at every bounce and
+/// combining it with scattered rays by multiple importance sampling.
+pub struct PathTracer {
+    bounces: usize,
+    t_max: f32,
+    guide: Option<Arc<Guide>>,
+}
+
+impl PathTracer {
+    pub fn new(bounces: usize, t_max: f32) -> Self {
+        Self {
+            bounces,
+            t_max,
+            guide: None,
+        }
+    }
+
+    /// Scatters rays off diffuse surfaces partly towards where `guide` has
+    /// found light, recording the light they find into it.
+    pub(crate) fn with_guide(self, guide: Arc<Guide>) -> Self {
+        Self {
+            guide: Some(guide),
+            ..self
+        }
+    }
+
+    /// `scatter_pdf` is the pdf with which the previous bounce picked `ray`,
+    /// if that bounce could also have sampled the background directly.
+    ///
+    /// Also returns the light arriving along `ray` before the background's
+    /// share is weighted against light sampling, for path guiding to learn
+    /// where light comes from.
+    fn trace(
+        &self,
+        scene: &Scene,
+        ray: Ray,
+        bounces: usize,
+        scatter_pdf: Option<f32>,
+        sampler: &mut dyn Sampler,
+    ) -> (Contribution, Color) {
+        if bounces == 0 {
+            return (Contribution::default(), Color::new(0.0, 0.0, 0.0));
+        }
+
+        match scene.hit(ray, 0.0, self.t_max) {
+            Some(record) => {
+                let contribution = self.shade(scene, ray, record, bounces, sampler);
+                (contribution, contribution.total())
+            }
+            None => (
+                Contribution::emitted(miss(scene, ray, scatter_pdf)),
+                scene.background().radiance(ray.direction),
+            ),
+        }
+    }
+
+    /// Light leaving a hit towards the origin of `ray`.
+    fn shade(
+        &self,
+        scene: &Scene,
+        ray: Ray,
+        mut record: HitRecord,
+        bounces: usize,
+        sampler: &mut dyn Sampler,
+    ) -> Contribution {
+        record.compute_footprint(&ray);
+
+        let material = record.material;
+        let guided = (self.guide.as_ref())
+            .filter(|_| material.lobe() == Lobe::Diffuse)
+            .map(|guide| guide.at(record.point));
+
+        let scattered = match &guided {
+            Some(guided) => guided.scatter(ray, record, sampler),
+            None => material
+                .scatter(ray, record, sampler)
+                .map(|(scattered, color1)| {
+                    let pdf = material
+                        .eval(ray, &record, scattered.direction)
+                        .map(|(_, pdf)| pdf);
+                    (scattered, color1, pdf)
+                }),
+        };
+
+        match scattered {
+            Some((scattered, color1, pdf)) => {
+                let mut direct = Color::new(0.0, 0.0, 0.0);
+
+                // Light sampling only helps if the scattered ray may still reach the background.
+                if bounces > 1 {
+                    let light = sample_background(scene, ray, &record, guided.as_ref(), sampler);
+                    direct = direct + light;
+                }
+
+                let (next, incoming) = if color1.near_zero() {
+                    (Contribution::default(), Color::new(0.0, 0.0, 0.0))
+                } else {
+                    self.trace(scene, scattered, bounces - 1, pdf, sampler)
+                };
+                if let Some(guided) = &guided {
+                    guided.record(scattered.direction, incoming, pdf);
+                }
+
+                Contribution {
+                    emitted: Color::new(0.0, 0.0, 0.0),
+                    direct: direct + color1 * next.emitted,
+                    indirect: color1 * (next.direct + next.indirect),
+                }
+            }
+            None => Contribution::emitted(material.emit(ray, &record)),
+        }
+    }
+}
+
+impl Integrator for PathTracer {
+    fn radiance(
+        &self,
+        scene: &Scene,
+        ray: Ray,
+        hit: Option<HitRecord>,
+        sampler: &mut dyn Sampler,
+        _splats: &mut Vec<Splat>,
+    ) -> Contribution {
+        if self.bounces == 0 {
+            return Contribution::default();
+        }
+
+        match hit {
+            Some(record) => self.shade(scene, ray, record, self.bounces, sampler),
+            None => Contribution::emitted(miss(scene, ray, None)),
+        }
+    }
+}
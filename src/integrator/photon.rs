@@ -0,0 +1,338 @@
+use super::{
+    emission, hit_emitter, miss, parallel, sample_background, sample_emission, sample_emitter,
+    Contribution, Emitters, Integrator, Splat,
+};
+use crate::material::Lobe;
+use crate::rng::mix;
+use crate::{Color, HitRecord, Point, Ray, RenderOptions, Sampler, Scene, Vec3};
+use std::f32::consts::PI;
+use std::ops::Range;
+
+/// Path tracing with caustics from a photon map (Jensen, "Realistic Image
+/// Synthesis Using Photon Mapping").
+///
+/// Before rendering, photons are traced from emissive objects through mirrors
+/// and glass, and stored where they land on diffuse surfaces. Camera paths
+/// are traced like `PathTracer`, also sampling emissive objects directly, and
+/// add the light of the photons within `radius` of each diffuse hit, while
+/// skipping the light that reaches a diffuse surface through mirrors and
+/// glass, which the photons account for.
+///
+/// Photons blur caustics over `radius`. `IntegratorKind::for_pass` shrinks it
+/// between the passes of a progressive render, so averaging the passes
+/// converges to a sharp image (Knaus and Zwicker, "Progressive Photon
+/// Mapping: A Probabilistic Approach").
+pub struct PhotonMapper {
+    bounces: usize,
+    t_max: f32,
+    radius: f32,
+    emitters: Emitters,
+    map: PhotonMap,
+}
+
+/// How a camera path reached a hit, which decides whether light emitted
+/// there is already carried by photons.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Reached {
+    /// From the camera, through mirrors and glass only.
+    Camera,
+    /// Straight from a diffuse surface.
+    Diffuse,
+    /// From a diffuse surface, through mirrors and glass.
+    Caustic,
+}
+
+impl PhotonMapper {
+    /// Traces `photons` photons from the emissive objects of `scene`.
+    pub fn new(scene: &Scene, options: RenderOptions, photons: usize, radius: f32) -> Self {
+        let emitters = Emitters::new(scene);
+        let map = PhotonMap::new(shoot(scene, &emitters, options, photons));
+        Self {
+            bounces: options.bounces,
+            t_max: options.clip_end,
+            radius,
+            emitters,
+            map,
+        }
+    }
+
+    fn trace(
+        &self,
+        scene: &Scene,
+        ray: Ray,
+        bounces: usize,
+        scatter_pdf: Option<f32>,
+        reached: Reached,
+        sampler: &mut dyn Sampler,
+    ) -> Contribution {
+        if bounces == 0 {
+            return Contribution::default();
+        }
+
+        let Some(mut record) = scene.hit(ray, 0.0, self.t_max) else {
+            return Contribution::emitted(miss(scene, ray, scatter_pdf));
+        };
+        if !record.material.is_emissive() {
+            return self.shade(scene, ray, record, bounces, reached, sampler);
+        }
+
+        // Photons already carry this light to the diffuse surface the path came from.
+        if reached == Reached::Caustic {
+            return Contribution::default();
+        }
+        record.compute_footprint(&ray);
+        Contribution::emitted(hit_emitter(&self.emitters, ray, &record, scatter_pdf))
+    }
+
+    /// Light leaving a hit towards the origin of `ray`.
+    fn shade(
+        &self,
+        scene: &Scene,
+        ray: Ray,
+        mut record: HitRecord,
+        bounces: usize,
+        reached: Reached,
+        sampler: &mut dyn Sampler,
+    ) -> Contribution {
+        record.compute_footprint(&ray);
+
+        let material = record.material;
+
+        let Some((scattered, color1)) = material.scatter(ray, record, sampler) else {
+            return Contribution::emitted(material.emit(ray, &record));
+        };
+
+        let diffuse = material.lobe() == Lobe::Diffuse;
+        let (caustics, next) = match (diffuse, reached) {
+            (true, _) => (self.estimate(ray, &record), Reached::Diffuse),
+            (false, Reached::Camera) => (Color::new(0.0, 0.0, 0.0), Reached::Camera),
+            (false, _) => (Color::new(0.0, 0.0, 0.0), Reached::Caustic),
+        };
+
+        let mut direct = Color::new(0.0, 0.0, 0.0);
+        if bounces > 1 {
+            direct = direct + sample_background(scene, ray, &record, None, sampler);
+            if next != Reached::Caustic {
+                let light = sample_emitter(scene, &self.emitters, ray, &record, sampler);
+                direct = direct + light;
+            }
+        }
+
+        let pdf = material
+            .eval(ray, &record, scattered.direction)
+            .map(|(_, pdf)| pdf);
+        let light = self.trace(scene, scattered, bounces - 1, pdf, next, sampler);
+
+        Contribution {
+            emitted: Color::new(0.0, 0.0, 0.0),
+            direct: direct + color1 * light.emitted,
+            indirect: caustics + color1 * (light.direct + light.indirect),
+        }
+    }
+
+    /// Light of the photons near a diffuse hit, leaving it towards the origin of `ray`.
+    fn estimate(&self, ray: Ray, record: &HitRecord) -> Color {
+        let mut sum = Color::new(0.0, 0.0, 0.0);
+        self.map
+            .for_each_within(record.point, self.radius, |photon| {
+                let incoming = -photon.direction;
+                let cosine = record.normal.dot(&incoming);
+                if cosine <= 0.0 {
+                    return;
+                }
+                // `eval` includes the cosine, which the density of photons already accounts for.
+                if let Some((f, _)) = record.material.eval(ray, record, incoming) {
+                    sum = sum + f * photon.power / cosine;
+                }
+            });
+        sum / (PI * self.radius * self.radius)
+    }
+}
+
+impl Integrator for PhotonMapper {
+    fn radiance(
+        &self,
+        scene: &Scene,
+        ray: Ray,
+        hit: Option<HitRecord>,
+        sampler: &mut dyn Sampler,
+        _splats: &mut Vec<Splat>,
+    ) -> Contribution {
+        if self.bounces == 0 {
+            return Contribution::default();
+        }
+
+        match hit {
+            Some(record) => self.shade(scene, ray, record, self.bounces, Reached::Camera, sampler),
+            None => Contribution::emitted(miss(scene, ray, None)),
+        }
+    }
+}
+
+/// Light arriving at a diffuse surface through mirrors and glass.
+#[derive(Debug, Clone, Copy)]
+struct Photon {
+    point: Point,
+    /// The direction the photon was travelling in.
+    direction: Vec3,
+    power: Color,
+}
+
+/// Traces `photons` photons from `emitters` on the render threads, returning
+/// those that landed on a diffuse surface after passing through mirrors or glass.
+fn shoot(
+    scene: &Scene,
+    emitters: &Emitters,
+    options: RenderOptions,
+    photons: usize,
+) -> Vec<Photon> {
+    if photons == 0 || emitters.objects.is_empty() {
+        return Vec::new();
+    }
+
+    // Photons are numbered like the samples of a single pixel, so the same
+    // seed gives the same photons however many threads trace them.
+    let seed = mix(options.seed);
+    parallel(options.thread_count(), photons, |range| {
+        let mut sampler = options.sampler.build(seed, photons);
+        let mut stored = Vec::new();
+        for index in range {
+            sampler.start_sample(0, 0, index);
+            if let Some(mut photon) = trace_photon(scene, emitters, options, &mut *sampler) {
+                photon.power = photon.power / photons as f32;
+                stored.push(photon);
+            }
+        }
+        stored
+    })
+}
+
+/// Traces a photon from a random point on an emitter until it lands on a
+/// diffuse surface, returning it if it passed through mirrors or glass on the way.
+fn trace_photon(
+    scene: &Scene,
+    emitters: &Emitters,
+    options: RenderOptions,
+    sampler: &mut dyn Sampler,
+) -> Option<Photon> {
+    let (record, area_pdf) = emitters.sample(scene, sampler)?;
+    let (direction, direction_pdf) = sample_emission(&record, sampler);
+    if direction_pdf == 0.0 {
+        return None;
+    }
+
+    let cosine = record.normal.dot(&direction).abs();
+    let mut power = emission(&record, direction) * cosine / (area_pdf * direction_pdf);
+    let mut ray = record.spawn_ray(direction);
+
+    for bounce in 0..options.bounces {
+        if power.near_zero() {
+            return None;
+        }
+
+        let mut record = scene.hit(ray, 0.0, options.clip_end)?;
+        record.compute_footprint(&ray);
+        let material = record.material;
+
+        if material.lobe() == Lobe::Diffuse {
+            // Light reaching diffuse surfaces directly is left to camera paths.
+            return (bounce > 0 && !material.is_emissive()).then_some(Photon {
+                point: record.point,
+                direction: ray.direction.unit(),
+                power,
+            });
+        }
+
+        let (scattered, weight) = material.scatter(ray, record, sampler)?;
+        power = power * weight * material.light_scale(ray, &record, scattered.direction);
+        ray = scattered;
+    }
+    None
+}
+
+/// Photons in a kd-tree. Each slice of the tree holds its node at its middle,
+/// splitting the photons before it from those after it along the node's axis.
+struct PhotonMap {
+    photons: Vec<Photon>,
+    axes: Vec<u8>,
+}
+
+impl PhotonMap {
+    fn new(mut photons: Vec<Photon>) -> Self {
+        let mut axes = vec![0; photons.len()];
+        balance(&mut photons, &mut axes);
+        Self { photons, axes }
+    }
+
+    fn for_each_within(&self, point: Point, radius: f32, mut f: impl FnMut(&Photon)) {
+        self.search(0..self.photons.len(), point, radius * radius, &mut f);
+    }
+
+    fn search(
+        &self,
+        range: Range<usize>,
+        point: Point,
+        radius_squared: f32,
+        f: &mut impl FnMut(&Photon),
+    ) {
+        if range.is_empty() {
+            return;
+        }
+
+        let middle = range.start + range.len() / 2;
+        let photon = &self.photons[middle];
+        let axis = self.axes[middle];
+        let distance = point.axis(axis as usize) - photon.point.axis(axis as usize);
+
+        let (near, far) = if distance < 0.0 {
+            (range.start..middle, middle + 1..range.end)
+        } else {
+            (middle + 1..range.end, range.start..middle)
+        };
+
+        self.search(near, point, radius_squared, f);
+        if distance * distance < radius_squared {
+            let offset = photon.point - point;
+            if offset.dot(&offset) < radius_squared {
+                f(photon);
+            }
+            self.search(far, point, radius_squared, f);
+        }
+    }
+}
+
+/// Arranges `photons` into a kd-tree, splitting each slice at the median
+/// along its longest axis.
+fn balance(photons: &mut [Photon], axes: &mut [u8]) {
+    if photons.len() <= 1 {
+        return;
+    }
+
+    let (mut min, mut max) = (photons[0].point, photons[0].point);
+    for photon in photons.iter() {
+        let p = photon.point;
+        min = Vec3::new(min.x.min(p.x), min.y.min(p.y), min.z.min(p.z));
+        max = Vec3::new(max.x.max(p.x), max.y.max(p.y), max.z.max(p.z));
+    }
+    let extent = max - min;
+    let axis = if extent.x >= extent.y && extent.x >= extent.z {
+        0
+    } else if extent.y >= extent.z {
+        1
+    } else {
+        2
+    };
+
+    let middle = photons.len() / 2;
+    photons.select_nth_unstable_by(middle, |a, b| {
+        a.point
+            .axis(axis as usize)
+            .total_cmp(&b.point.axis(axis as usize))
+    });
+    axes[middle] = axis;
+
+    let (before, after) = photons.split_at_mut(middle);
+    let (axes_before, axes_after) = axes.split_at_mut(middle);
+    balance(before, axes_before);
+    balance(&mut after[1..], &mut axes_after[1..]);
+}
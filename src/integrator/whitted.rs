@@ -0,0 +1,73 @@
+use super::{Contribution, Integrator, PathTracer, Splat};
+use crate::material::Lobe;
+use crate::{Color, HitRecord, Ray, Sampler, Scene};
+
+/// Whitted-style ray tracing: rays are followed through mirrors and glass,
+/// and stop at the first diffuse surface, which only receives direct light.
+pub struct Whitted {
+    depth: usize,
+    t_max: f32,
+    direct: PathTracer,
+}
+
+impl Whitted {
+    /// Follows at most `depth` reflections and refractions.
+    pub fn new(depth: usize, t_max: f32) -> Self {
+        Self {
+            depth,
+            t_max,
+            direct: PathTracer::new(2, t_max),
+        }
+    }
+
+    fn trace(
+        &self,
+        scene: &Scene,
+        ray: Ray,
+        hit: Option<HitRecord>,
+        depth: usize,
+        sampler: &mut dyn Sampler,
+        splats: &mut Vec<Splat>,
+    ) -> Contribution {
+        if depth == 0 {
+            return Contribution::default();
+        }
+
+        let Some(mut record) = hit else {
+            return Contribution::emitted(scene.background().radiance(ray.direction));
+        };
+
+        if record.material.lobe() == Lobe::Diffuse {
+            return self
+                .direct
+                .radiance(scene, ray, Some(record), sampler, splats);
+        }
+
+        record.compute_footprint(&ray);
+        match record.material.scatter(ray, record, sampler) {
+            Some((scattered, attenuation)) => {
+                let hit = scene.hit(scattered, 0.0, self.t_max);
+                let next = self.trace(scene, scattered, hit, depth - 1, sampler, splats);
+                Contribution {
+                    emitted: Color::new(0.0, 0.0, 0.0),
+                    direct: attenuation * next.emitted,
+                    indirect: attenuation * (next.direct + next.indirect),
+                }
+            }
+            None => Contribution::emitted(record.material.emit(ray, &record)),
+        }
+    }
+}
+
+impl Integrator for Whitted {
+    fn radiance(
+        &self,
+        scene: &Scene,
+        ray: Ray,
+        hit: Option<HitRecord>,
+        sampler: &mut dyn Sampler,
+        splats: &mut Vec<Splat>,
+    ) -> Contribution {
+        self.trace(scene, ray, hit, self.depth, sampler, splats)
+    }
+}
@@ -0,0 +1,105 @@
+//! OpenEXR (`.exr`) images.
+
+use std::io::{self, Write};
+
+const MAGIC: u32 = 20000630;
+
+/// Writes an uncompressed, single part scanline image with 32-bit float channels.
+///
+/// Each channel is a name (such as `R`, or `diffuse.R` for a layered image)
+/// and `width * height` values in row-major order.
+pub fn write<W: Write>(
+    mut writer: W,
+    width: usize,
+    height: usize,
+    channels: &[(&str, &[f32])],
+) -> io::Result<()> {
+    for (_, values) in channels {
+        if values.len() != width * height {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "channel size does not match image size",
+            ));
+        }
+    }
+
+    // Channels must be stored in alphabetical order.
+    let mut channels = channels.to_vec();
+    channels.sort_by(|a, b| a.0.cmp(b.0));
+
+    let mut header = Vec::new();
+    header.extend_from_slice(&MAGIC.to_le_bytes());
+    header.extend_from_slice(&2u32.to_le_bytes());
+
+    let mut chlist = Vec::new();
+    for (name, _) in channels.iter() {
+        chlist.extend_from_slice(name.as_bytes());
+        chlist.push(0);
+        chlist.extend_from_slice(&2i32.to_le_bytes()); // FLOAT
+        chlist.extend_from_slice(&[0, 0, 0, 0]); // pLinear and reserved
+        chlist.extend_from_slice(&1i32.to_le_bytes());
+        chlist.extend_from_slice(&1i32.to_le_bytes());
+    }
+    chlist.push(0);
+
+    let window: Vec<u8> = [0, 0, width as i32 - 1, height as i32 - 1]
+        .iter()
+        .flat_map(|v| v.to_le_bytes())
+        .collect();
+
+    attribute(&mut header, "channels", "chlist", &chlist);
+    attribute(&mut header, "compression", "compression", &[0]);
+    attribute(&mut header, "dataWindow", "box2i", &window);
+    attribute(&mut header, "displayWindow", "box2i", &window);
+    attribute(&mut header, "lineOrder", "lineOrder", &[0]);
+    attribute(
+        &mut header,
+        "pixelAspectRatio",
+        "float",
+        &1f32.to_le_bytes(),
+    );
+    attribute(&mut header, "screenWindowCenter", "v2f", &[0; 8]);
+    attribute(
+        &mut header,
+        "screenWindowWidth",
+        "float",
+        &1f32.to_le_bytes(),
+    );
+    header.push(0);
+
+    // One scanline per chunk, each preceded by its y coordinate and size.
+    let line_size = width * channels.len() * 4;
+    let chunk_size = 8 + line_size;
+    let first_chunk = header.len() + height * 8;
+
+    writer.write_all(&header)?;
+    for y in 0..height {
+        let offset = (first_chunk + y * chunk_size) as u64;
+        writer.write_all(&offset.to_le_bytes())?;
+    }
+
+    let mut line = Vec::with_capacity(line_size);
+    for y in 0..height {
+        line.clear();
+        for (_, values) in channels.iter() {
+            for value in &values[y * width..(y + 1) * width] {
+                line.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+
+        writer.write_all(&(y as i32).to_le_bytes())?;
+        writer.write_all(&(line_size as i32).to_le_bytes())?;
+        writer.write_all(&line)?;
+    }
+
+    writer.flush()
+}
+
+fn attribute(header: &mut Vec<u8>, name: &str, kind: &str, value: &[u8]) {
+    header.extend_from_slice(name.as_bytes());
+    header.push(0);
+    header.extend_from_slice(kind.as_bytes());
+    header.push(0);
+    header.extend_from_slice(&(value.len() as i32).to_le_bytes());
+    header.extend_from_slice(value);
+}
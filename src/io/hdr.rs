@@ -0,0 +1,110 @@
+//! Radiance RGBE (`.hdr`) images.
+
+use super::{invalid_data, read_line};
+use crate::{Color, HdrImage};
+use std::io::{self, BufRead};
+
+/// Reads a Radiance `.hdr` image, supporting both flat and run-length encoded scanlines.
+pub fn read<R: BufRead>(mut reader: R) -> io::Result<HdrImage> {
+    let magic = read_line(&mut reader)?;
+    if !magic.starts_with("#?") {
+        return Err(invalid_data("not a Radiance HDR file"));
+    }
+
+    loop {
+        let line = read_line(&mut reader)?;
+        if line.is_empty() {
+            break;
+        }
+        if let Some(format) = line.strip_prefix("FORMAT=") {
+            if format != "32-bit_rle_rgbe" {
+                return Err(invalid_data("unsupported HDR pixel format"));
+            }
+        }
+    }
+
+    let resolution = read_line(&mut reader)?;
+    let (width, height) = match resolution.split_whitespace().collect::<Vec<_>>()[..] {
+        ["-Y", h, "+X", w] => (parse(w)?, parse(h)?),
+        _ => return Err(invalid_data("unsupported HDR orientation")),
+    };
+
+    let mut image = HdrImage::new(width, height);
+    let mut scanline = vec![[0u8; 4]; width];
+
+    for y in 0..height {
+        read_scanline(&mut reader, &mut scanline)?;
+        for (x, rgbe) in scanline.iter().enumerate() {
+            image.set_pixel(x, y, rgbe_to_color(*rgbe));
+        }
+    }
+
+    Ok(image)
+}
+
+fn parse(s: &str) -> io::Result<usize> {
+    s.parse()
+        .map_err(|_| invalid_data("invalid HDR resolution"))
+}
+
+fn read_scanline<R: BufRead>(reader: &mut R, scanline: &mut [[u8; 4]]) -> io::Result<()> {
+    let width = scanline.len();
+    let mut first = [0u8; 4];
+    reader.read_exact(&mut first)?;
+
+    let is_rle = (8..0x8000).contains(&width) && first[0] == 2 && first[1] == 2;
+    if !is_rle || ((first[2] as usize) << 8 | first[3] as usize) != width {
+        scanline[0] = first;
+        for pixel in scanline.iter_mut().skip(1) {
+            reader.read_exact(pixel)?;
+        }
+        return Ok(());
+    }
+
+    // Each channel is stored separately as a sequence of runs and literals.
+    for channel in 0..4 {
+        let mut x = 0;
+        while x < width {
+            let mut count = [0u8; 1];
+            reader.read_exact(&mut count)?;
+
+            if count[0] > 128 {
+                let run = (count[0] - 128) as usize;
+                let mut value = [0u8; 1];
+                reader.read_exact(&mut value)?;
+                if x + run > width {
+                    return Err(invalid_data("corrupt HDR scanline"));
+                }
+                for pixel in &mut scanline[x..x + run] {
+                    pixel[channel] = value[0];
+                }
+                x += run;
+            } else {
+                let run = count[0] as usize;
+                if run == 0 || x + run > width {
+                    return Err(invalid_data("corrupt HDR scanline"));
+                }
+                let mut values = vec![0u8; run];
+                reader.read_exact(&mut values)?;
+                for (pixel, value) in scanline[x..x + run].iter_mut().zip(values) {
+                    pixel[channel] = value;
+                }
+                x += run;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn rgbe_to_color([r, g, b, e]: [u8; 4]) -> Color {
+    if e == 0 {
+        return Color::new(0.0, 0.0, 0.0);
+    }
+    let scale = 2f32.powi(e as i32 - (128 + 8));
+    Color::new(
+        (r as f32 + 0.5) * scale,
+        (g as f32 + 0.5) * scale,
+        (b as f32 + 0.5) * scale,
+    )
+}
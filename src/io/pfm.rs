@@ -0,0 +1,73 @@
+//! Portable float maps (`.pfm`).
+
+use super::{invalid_data, read_line};
+use crate::{Color, HdrImage};
+use std::io::{self, BufRead, Write};
+
+/// Reads a color (`PF`) or greyscale (`Pf`) portable float map.
+pub fn read<R: BufRead>(mut reader: R) -> io::Result<HdrImage> {
+    let channels = match read_line(&mut reader)?.trim() {
+        "PF" => 3,
+        "Pf" => 1,
+        _ => return Err(invalid_data("not a PFM file")),
+    };
+
+    let dimensions = read_line(&mut reader)?;
+    let mut dimensions = dimensions.split_whitespace().map(|s| s.parse::<usize>());
+    let (width, height) = match (dimensions.next(), dimensions.next()) {
+        (Some(Ok(w)), Some(Ok(h))) => (w, h),
+        _ => return Err(invalid_data("invalid PFM dimensions")),
+    };
+
+    let scale: f32 = read_line(&mut reader)?
+        .trim()
+        .parse()
+        .map_err(|_| invalid_data("invalid PFM scale"))?;
+    let little_endian = scale < 0.0;
+
+    let mut image = HdrImage::new(width, height);
+    let mut row = vec![0u8; width * channels * 4];
+
+    // Scanlines are stored bottom to top.
+    for y in (0..height).rev() {
+        reader.read_exact(&mut row)?;
+        let values: Vec<f32> = row
+            .chunks_exact(4)
+            .map(|b| {
+                let bytes = [b[0], b[1], b[2], b[3]];
+                if little_endian {
+                    f32::from_le_bytes(bytes)
+                } else {
+                    f32::from_be_bytes(bytes)
+                }
+            })
+            .collect();
+
+        for x in 0..width {
+            let color = if channels == 3 {
+                Color::new(values[3 * x], values[3 * x + 1], values[3 * x + 2])
+            } else {
+                Color::new(values[x], values[x], values[x])
+            };
+            image.set_pixel(x, y, color);
+        }
+    }
+
+    Ok(image)
+}
+
+/// Writes a little endian color portable float map.
+pub fn write<W: Write>(mut writer: W, image: &HdrImage) -> io::Result<()> {
+    write!(writer, "PF\n{} {}\n-1.0\n", image.width(), image.height())?;
+
+    for y in (0..image.height()).rev() {
+        for x in 0..image.width() {
+            let color = image.get_pixel(x, y);
+            for value in [color.x, color.y, color.z] {
+                writer.write_all(&value.to_le_bytes())?;
+            }
+        }
+    }
+
+    writer.flush()
+}
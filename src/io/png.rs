@@ -0,0 +1,23 @@
+//! Portable network graphics (`.png`) images.
+
+use crate::Image;
+use std::io::{self, Write};
+
+/// Writes an 8-bit RGB PNG image.
+pub fn write<W: Write>(writer: W, image: &Image) -> io::Result<()> {
+    let mut encoder = ::png::Encoder::new(writer, image.width() as u32, image.height() as u32);
+    encoder.set_color(::png::ColorType::Rgb);
+    encoder.set_depth(::png::BitDepth::Eight);
+
+    let mut data = Vec::with_capacity(image.width() * image.height() * 3);
+    for y in 0..image.height() {
+        for x in 0..image.width() {
+            let pixel = image.get_pixel(x, y);
+            data.extend_from_slice(&[pixel.r, pixel.g, pixel.b]);
+        }
+    }
+
+    let mut writer = encoder.write_header().map_err(io::Error::other)?;
+    writer.write_image_data(&data).map_err(io::Error::other)?;
+    writer.finish().map_err(io::Error::other)
+}
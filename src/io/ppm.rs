@@ -0,0 +1,17 @@
+//! Binary portable pixmaps (`.ppm`).
+
+use crate::Image;
+use std::io::{self, Write};
+
+pub fn write<W: Write>(mut writer: W, image: &Image) -> io::Result<()> {
+    write!(writer, "P6\n{} {}\n255\n", image.width(), image.height())?;
+
+    for y in 0..image.height() {
+        for x in 0..image.width() {
+            let pixel = image.get_pixel(x, y);
+            writer.write_all(&[pixel.r, pixel.g, pixel.b])?;
+        }
+    }
+
+    writer.flush()
+}
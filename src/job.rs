@@ -0,0 +1,106 @@
+//! Renders running in the background.
+
+use super::tile::tiles;
+use super::{RenderOptions, RenderResult};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+/// A handle to a render started with `Scene::spawn_render`.
+pub struct RenderJob {
+    state: Arc<JobState>,
+    handle: JoinHandle<RenderResult>,
+}
+
+impl RenderJob {
+    pub(crate) fn new(state: Arc<JobState>, handle: JoinHandle<RenderResult>) -> Self {
+        Self { state, handle }
+    }
+
+    pub fn completed_tiles(&self) -> usize {
+        self.state.completed_tiles.load(Ordering::Relaxed)
+    }
+
+    pub fn total_tiles(&self) -> usize {
+        self.state.total_tiles
+    }
+
+    /// Number of samples taken so far, over all pixels.
+    pub fn samples(&self) -> usize {
+        self.state.samples.load(Ordering::Relaxed)
+    }
+
+    /// Fraction of pixels that are finished, between 0 and 1.
+    pub fn progress(&self) -> f32 {
+        let pixels = self.state.completed_pixels.load(Ordering::Relaxed);
+        match self.state.total_pixels {
+            0 => 1.0,
+            total => pixels as f32 / total as f32,
+        }
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.state.start.elapsed()
+    }
+
+    /// Estimated time until the render finishes, assuming the remaining pixels
+    /// take as long as the finished ones. `None` until a pixel has finished.
+    pub fn remaining(&self) -> Option<Duration> {
+        let progress = self.progress();
+        (progress > 0.0).then(|| self.elapsed().mul_f32((1.0 - progress) / progress))
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.handle.is_finished()
+    }
+
+    /// Asks the render to stop. Pixels that were not rendered yet are left
+    /// black and transparent in the result returned by `wait`.
+    pub fn cancel(&self) {
+        self.state.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    /// Blocks until the render finishes or stops after being cancelled.
+    pub fn wait(self) -> RenderResult {
+        self.handle.join().unwrap()
+    }
+}
+
+/// Progress shared between a render's threads and its `RenderJob`.
+pub(crate) struct JobState {
+    pub start: Instant,
+    /// When pixels should stop sampling early, if sampling adaptively.
+    pub deadline: Option<Instant>,
+    pub cancelled: AtomicBool,
+    pub completed_tiles: AtomicUsize,
+    pub total_tiles: usize,
+    pub completed_pixels: AtomicUsize,
+    pub total_pixels: usize,
+    pub samples: AtomicUsize,
+}
+
+impl JobState {
+    pub fn new(options: RenderOptions) -> Self {
+        let start = Instant::now();
+        let ((x_start, x_end), (y_start, y_end)) = options.crop_region;
+
+        let budget = options.adaptive.and_then(|adaptive| adaptive.time_budget);
+        let total_tiles = tiles(options.crop_region, options.block_size, options.tile_order).len();
+
+        Self {
+            start,
+            deadline: budget.map(|budget| start + budget),
+            cancelled: AtomicBool::new(false),
+            completed_tiles: AtomicUsize::new(0),
+            total_tiles,
+            completed_pixels: AtomicUsize::new(0),
+            total_pixels: (x_end - x_start) * (y_end - y_start),
+            samples: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
@@ -1,17 +1,41 @@
 mod vec3;
 pub use vec3::Vec3;
 
-mod ray;
+pub mod ray;
 pub use ray::Ray;
 
 mod camera;
 pub use camera::Camera;
 
 mod scene;
-pub use scene::{RenderOptions, Scene};
+pub use scene::{AdaptiveSampling, Progress, RenderOptions, Scene};
+
+pub mod integrator;
+pub use integrator::{Integrator, IntegratorKind};
+
+mod job;
+pub use job::RenderJob;
+
+mod tile;
+pub use tile::TileOrder;
+
+mod filter;
+pub use filter::Filter;
+
+mod denoise;
+pub use denoise::Denoiser;
+
+mod guiding;
+pub use guiding::PathGuiding;
+
+mod firefly;
+pub use firefly::{Accumulation, Clamp};
+
+mod pass;
+pub use pass::{Pass, Passes, RenderResult};
 
 mod image;
-pub use image::{Image, Pixel};
+pub use image::{HdrImage, Image, Pixel};
 
 pub mod texture;
 pub use texture::Texture;
@@ -25,11 +49,22 @@ pub use material::Material;
 pub mod mesh;
 pub use mesh::{Mesh, Trig};
 
+pub mod background;
+pub use background::Background;
+
+pub mod io;
+
+pub mod tonemap;
+pub use tonemap::{OutputSettings, Tonemapper};
+
+mod distribution;
+
+mod rng;
+pub use rng::Rng;
+
+pub mod sampler;
+pub use sampler::{Sampler, SamplerKind};
+
 // Aliases
 pub type Point = Vec3;
 pub type Color = Vec3;
-
-pub fn random(min: f32, max: f32) -> f32 {
-    use rand::Rng;
-    rand::thread_rng().gen_range(min..max)
-}
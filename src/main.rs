@@ -17,6 +17,8 @@ const CLIP_START: f32 = 0.01;
 const CLIP_END: f32 = f32::INFINITY;
 const BLOCK_SIZE: usize = 128;
 
+const EXPOSURE: f32 = 0.0;
+
 fn main() {
     let camera = Camera::new(
         LOOKFROM,
@@ -69,21 +71,30 @@ fn main() {
         crop_region: ((0, WIDTH), (0, HEIGHT)),
         samples: SAMPLES_PER_PIXEL,
         bounces: BOUNCES,
+        seed: 0,
+        sampler: SamplerKind::Sobol,
+        integrator: IntegratorKind::PathTracer,
         clip_start: CLIP_START,
         clip_end: CLIP_END,
         block_size: BLOCK_SIZE,
+        threads: None,
+        tile_order: TileOrder::Spiral,
+        passes: Passes::NONE,
+        denoise: None,
+        adaptive: None,
+        filter: Filter::default(),
+        guiding: None,
+        clamp: None,
+        accumulation: Accumulation::Mean,
     };
 
     let scene = Scene::new(camera, Box::new(sky), objects);
-    let image = scene.threaded_render(options);
-
-    let mut bitmap = bmp::Image::new(WIDTH as u32, HEIGHT as u32);
-    for y in 0..HEIGHT {
-        for x in 0..WIDTH {
-            let ocular::Pixel { r, g, b } = image.get_pixel(x, y);
-            bitmap.set_pixel(x as u32, y as u32, bmp::Pixel { r, g, b });
-        }
-    }
+    let output = OutputSettings {
+        exposure: EXPOSURE,
+        white_balance: None,
+        tonemapper: Tonemapper::Aces,
+    };
 
-    bitmap.save("assets/result.bmp").unwrap();
+    let image = scene.threaded_render(options).beauty.to_image(&output);
+    image.save("assets/result.bmp").unwrap();
 }
@@ -8,11 +8,59 @@ pub use glass::Glass;
 pub use light::Light;
 pub use metal::Metal;
 
-use super::{Color, HitRecord, Point, Ray};
+use super::{Color, HitRecord, Ray, Sampler, Vec3};
+
+/// The kind of scattering a material performs, used to split lighting into passes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Lobe {
+    Diffuse,
+    Glossy,
+    Transmission,
+}
 
 pub trait Material: Send + Sync {
-    fn scatter(&self, ray: Ray, hit_record: HitRecord) -> Option<(Ray, Color)>;
-    fn emit(&self, _u: f32, _v: f32, _point: Point) -> Color {
+    fn scatter(
+        &self,
+        ray: Ray,
+        hit_record: HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(Ray, Color)>;
+
+    /// Evaluates scattering towards `direction`, returning the BSDF multiplied
+    /// by the cosine term, and the pdf with which `scatter` picks that direction.
+    ///
+    /// Materials whose scattering cannot be evaluated (such as perfect mirrors)
+    /// return `None`, and are not used for light sampling.
+    fn eval(&self, _ray: Ray, _hit_record: &HitRecord, _direction: Vec3) -> Option<(Color, f32)> {
+        None
+    }
+
+    /// The color of the surface without lighting, used for albedo passes.
+    fn albedo(&self, _hit_record: &HitRecord) -> Color {
+        Color::new(0.0, 0.0, 0.0)
+    }
+
+    fn lobe(&self) -> Lobe {
+        Lobe::Diffuse
+    }
+
+    /// Turns the weight `scatter` returned for `scattered` into the weight for
+    /// light travelling the same way, as traced from emissive objects.
+    ///
+    /// `scatter` weights rays traced from the camera; the two only differ
+    /// where refraction squeezes or spreads light out.
+    fn light_scale(&self, _ray: Ray, _hit_record: &HitRecord, _scattered: Vec3) -> f32 {
+        1.0
+    }
+
+    /// Whether the material emits light, making the objects using it starting
+    /// points for light in bidirectional rendering.
+    fn is_emissive(&self) -> bool {
+        false
+    }
+
+    /// Light emitted from the hit towards the origin of `ray`.
+    fn emit(&self, _ray: Ray, _hit_record: &HitRecord) -> Color {
         Color::new(0.0, 0.0, 0.0)
     }
 }
@@ -1,5 +1,6 @@
 use super::Material;
-use crate::{Color, HitRecord, Ray, Texture, Vec3};
+use crate::{Color, HitRecord, Ray, Sampler, Texture, Vec3};
+use std::f32::consts::PI;
 
 pub struct Diffuse {
     texture: Box<dyn Texture>,
@@ -12,18 +13,33 @@ impl Diffuse {
 }
 
 impl Material for Diffuse {
-    fn scatter(&self, _ray: Ray, hit_record: HitRecord) -> Option<(Ray, Color)> {
-        let (u, v) = hit_record.uv;
-        let color = self.texture.color(u, v, hit_record.point);
+    fn albedo(&self, hit_record: &HitRecord) -> Color {
+        self.texture.color_at(hit_record)
+    }
+
+    fn scatter(
+        &self,
+        _ray: Ray,
+        hit_record: HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(Ray, Color)> {
+        let color = self.texture.color_at(&hit_record);
 
-        let mut target = hit_record.normal + Vec3::random_in_unit_sphere();
+        let mut target = hit_record.normal + Vec3::random_unit_vector(sampler);
 
         if target.near_zero() {
             target = hit_record.normal;
         }
 
-        let scattered = Ray::new(hit_record.point, target);
+        let scattered = hit_record.spawn_ray(target);
 
         Some((scattered, color))
     }
+
+    fn eval(&self, _ray: Ray, hit_record: &HitRecord, direction: Vec3) -> Option<(Color, f32)> {
+        let color = self.texture.color_at(hit_record);
+
+        let cosine = hit_record.normal.dot(&direction.unit()).max(0.0);
+        Some((color * cosine / PI, cosine / PI))
+    }
 }
@@ -1,5 +1,5 @@
-use super::Material;
-use crate::{random, Color, HitRecord, Ray};
+use super::{Lobe, Material};
+use crate::{ray::Differential, Color, HitRecord, Ray, Sampler, Vec3};
 
 pub struct Glass {
     refractive_index: f32,
@@ -12,7 +12,20 @@ impl Glass {
 }
 
 impl Material for Glass {
-    fn scatter(&self, ray: Ray, hit_record: HitRecord) -> Option<(Ray, Color)> {
+    fn lobe(&self) -> Lobe {
+        Lobe::Transmission
+    }
+
+    fn albedo(&self, _hit_record: &HitRecord) -> Color {
+        Color::new(1.0, 1.0, 1.0)
+    }
+
+    fn scatter(
+        &self,
+        ray: Ray,
+        hit_record: HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(Ray, Color)> {
         let color = Color::new(1.0, 1.0, 1.0);
 
         let ri_inverse = if hit_record.front_face {
@@ -26,16 +39,39 @@ impl Material for Glass {
         let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
 
         let cannot_refract = ri_inverse * sin_theta > 1.0;
-        let target = if cannot_refract || schlick(cos_theta, ri_inverse) > random(0.0, 1.0) {
-            unit_direction.reflect(hit_record.normal)
-        } else {
-            unit_direction.refract(hit_record.normal, ri_inverse)
-        };
+        let (target, differential) =
+            if cannot_refract || schlick(cos_theta, ri_inverse) > sampler.next_1d() {
+                let target = unit_direction.reflect(hit_record.normal);
+                (target, Differential::reflect(&ray, &hit_record, target))
+            } else {
+                let target = unit_direction.refract(hit_record.normal, ri_inverse);
+                let differential = Differential::refract(&ray, &hit_record, target, ri_inverse);
+                (target, differential)
+            };
 
-        let scattered = Ray::new(hit_record.point, target);
+        let scattered = match differential {
+            Some(differential) => {
+                Ray::with_differential(hit_record.offset_point(target), target, differential)
+            }
+            None => hit_record.spawn_ray(target),
+        };
 
         Some((scattered, color))
     }
+
+    fn light_scale(&self, _ray: Ray, hit_record: &HitRecord, scattered: Vec3) -> f32 {
+        if scattered.dot(&hit_record.normal) >= 0.0 {
+            return 1.0;
+        }
+
+        // Refracted, from the side the normal faces to the other.
+        let ratio = if hit_record.front_face {
+            1.0 / self.refractive_index
+        } else {
+            self.refractive_index
+        };
+        ratio * ratio
+    }
 }
 
 fn schlick(cosine: f32, ri: f32) -> f32 {
@@ -1,22 +1,51 @@
 use super::Material;
-use crate::{Color, HitRecord, Point, Ray, Texture};
+use crate::{texture::Projection, Color, HitRecord, Ray, Sampler, Texture};
 
 pub struct Light {
-    texture: Box<dyn Texture>,
+    emission: Emission,
+}
+
+enum Emission {
+    Surface(Box<dyn Texture>),
+    Projected(Projection),
 }
 
 impl Light {
     pub fn new(texture: Box<dyn Texture>) -> Self {
-        Self { texture }
+        Self {
+            emission: Emission::Surface(texture),
+        }
+    }
+
+    /// A light whose emission depends on direction, shining `projection` into
+    /// the scene like a slide projector or a patterned spotlight.
+    ///
+    /// Works best with small lights placed at the projector's position.
+    pub fn projected(projection: Projection) -> Self {
+        Self {
+            emission: Emission::Projected(projection),
+        }
     }
 }
 
 impl Material for Light {
-    fn scatter(&self, _ray: Ray, _hit_record: HitRecord) -> Option<(Ray, Color)> {
+    fn scatter(
+        &self,
+        _ray: Ray,
+        _hit_record: HitRecord,
+        _sampler: &mut dyn Sampler,
+    ) -> Option<(Ray, Color)> {
         None
     }
 
-    fn emit(&self, u: f32, v: f32, point: Point) -> Color {
-        self.texture.color(u, v, point)
+    fn is_emissive(&self) -> bool {
+        true
+    }
+
+    fn emit(&self, ray: Ray, hit_record: &HitRecord) -> Color {
+        match &self.emission {
+            Emission::Surface(texture) => texture.color_at(hit_record),
+            Emission::Projected(projection) => projection.color_towards(-ray.direction),
+        }
     }
 }
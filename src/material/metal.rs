@@ -1,5 +1,5 @@
-use super::Material;
-use crate::{Color, HitRecord, Ray, Texture, Vec3};
+use super::{Lobe, Material};
+use crate::{ray::Differential, Color, HitRecord, Ray, Sampler, Texture, Vec3};
 
 pub struct Metal {
     texture: Box<dyn Texture>,
@@ -13,13 +13,33 @@ impl Metal {
 }
 
 impl Material for Metal {
-    fn scatter(&self, ray: Ray, hit_record: HitRecord) -> Option<(Ray, Color)> {
-        let (u, v) = hit_record.uv;
-        let color = self.texture.color(u, v, hit_record.point);
+    fn lobe(&self) -> Lobe {
+        Lobe::Glossy
+    }
+
+    fn albedo(&self, hit_record: &HitRecord) -> Color {
+        self.texture.color_at(hit_record)
+    }
+
+    fn scatter(
+        &self,
+        ray: Ray,
+        hit_record: HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(Ray, Color)> {
+        let color = self.texture.color_at(&hit_record);
 
         let target = ray.direction.reflect(hit_record.normal);
-        let random = self.roughness * Vec3::random_in_unit_sphere();
-        let scattered = Ray::new(hit_record.point, target + random);
+        let random = self.roughness * Vec3::random_in_unit_sphere(sampler);
+        let direction = target + random;
+
+        // Rough reflections are treated as mirrors, which underestimates their spread.
+        let scattered = match Differential::reflect(&ray, &hit_record, direction) {
+            Some(differential) => {
+                Ray::with_differential(hit_record.offset_point(direction), direction, differential)
+            }
+            None => hit_record.spawn_ray(direction),
+        };
 
         Some((scattered, color))
     }
@@ -1,4 +1,5 @@
-use super::{HitRecord, Hittable, Material, Point, Ray, Vec3};
+use super::object::gamma;
+use super::{HitRecord, Hittable, Material, Point, Ray, Sampler, Vec3};
 use obj::Obj;
 
 pub struct TrigHitRecord {
@@ -7,6 +8,10 @@ pub struct TrigHitRecord {
     pub front_face: bool,
     pub t: f32,
     pub uv: (f32, f32),
+    pub dpdu: Vec3,
+    pub dpdv: Vec3,
+    /// Bound on the floating point error in each coordinate of `point`.
+    pub error: Vec3,
 }
 
 pub struct Trig {
@@ -20,43 +25,116 @@ impl Trig {
         Self { v1, v2, v3 }
     }
 
-    pub fn hit(&self, ray: Ray) -> Option<TrigHitRecord> {
+    pub fn area(&self) -> f32 {
+        (self.v2 - self.v1).cross(&(self.v3 - self.v1)).len() / 2.0
+    }
+
+    /// Picks a point uniformly over the triangle, as a hit on its front.
+    pub fn sample(&self, sampler: &mut dyn Sampler) -> TrigHitRecord {
+        let (a, b) = sampler.next_2d();
+        let (u, v) = (a.sqrt() * (1.0 - b), a.sqrt() * b);
+
         let edge1 = self.v2 - self.v1;
         let edge2 = self.v3 - self.v1;
+        let w = 1.0 - u - v;
+        let (p1, p2, p3) = (self.v1 * w, self.v2 * u, self.v3 * v);
+        TrigHitRecord {
+            point: p1 + p2 + p3,
+            normal: edge1.cross(&edge2).unit(),
+            front_face: true,
+            t: 0.0,
+            uv: (u, v),
+            dpdu: edge1,
+            dpdv: edge2,
+            error: gamma(6) * (p1.abs() + p2.abs() + p3.abs()),
+        }
+    }
+
+    /// Watertight intersection (Woop et al., "Watertight Ray/Triangle
+    /// Intersection"), which never lets a ray slip between triangles sharing
+    /// an edge, and only reports hits certainly in front of the ray's origin.
+    pub fn hit(&self, ray: Ray) -> Option<TrigHitRecord> {
+        let d = ray.direction;
 
-        let (p, d) = (ray.origin, ray.direction);
-        let h = d.cross(&edge2);
-        let a = edge1.dot(&h);
+        // Moves the ray to the origin, pointing along +z, with the vertices
+        // moved to match.
+        let kz = if d.x.abs() > d.y.abs() && d.x.abs() > d.z.abs() {
+            0
+        } else if d.y.abs() > d.z.abs() {
+            1
+        } else {
+            2
+        };
+        let (kx, ky) = ((kz + 1) % 3, (kz + 2) % 3);
+        let permute = |v: Vec3| Vec3::new(v.axis(kx), v.axis(ky), v.axis(kz));
+        let d = permute(d);
+        let (sx, sy, sz) = (-d.x / d.z, -d.y / d.z, 1.0 / d.z);
+        let shear = |v: Point| {
+            let v = permute(v - ray.origin);
+            Vec3::new(v.x + sx * v.z, v.y + sy * v.z, v.z)
+        };
+        let (p1, p2, p3) = (shear(self.v1), shear(self.v2), shear(self.v3));
 
-        if a.abs() < 0.00001 {
+        // Edge functions, which are exactly zero for rays through an edge,
+        // checked again in double precision in that case.
+        let edge = |a: Vec3, b: Vec3| {
+            let e = a.x * b.y - a.y * b.x;
+            if e == 0.0 {
+                (a.x as f64 * b.y as f64 - a.y as f64 * b.x as f64) as f32
+            } else {
+                e
+            }
+        };
+        let (e1, e2, e3) = (edge(p2, p3), edge(p3, p1), edge(p1, p2));
+        if (e1 < 0.0 || e2 < 0.0 || e3 < 0.0) && (e1 > 0.0 || e2 > 0.0 || e3 > 0.0) {
+            return None;
+        }
+        let det = e1 + e2 + e3;
+        if det == 0.0 {
             return None;
         }
 
-        let f = 1.0 / a;
-        let s = p - self.v1;
-        let u = f * s.dot(&h);
-        if u < 0.0 || u > 1.0 {
+        let (z1, z2, z3) = (p1.z * sz, p2.z * sz, p3.z * sz);
+        let t_scaled = e1 * z1 + e2 * z2 + e3 * z3;
+        if (det < 0.0 && t_scaled >= 0.0) || (det > 0.0 && t_scaled <= 0.0) {
             return None;
         }
+        let inv_det = 1.0 / det;
+        let (b1, b2, b3) = (e1 * inv_det, e2 * inv_det, e3 * inv_det);
+        let t = t_scaled * inv_det;
 
-        let q = s.cross(&edge1);
-        let v = f * d.dot(&q);
-        if v < 0.0 || u + v > 1.0 {
+        // Rejects hits too close to the origin for `t` to be certainly
+        // positive, given the error in computing it.
+        let max_z = z1.abs().max(z2.abs()).max(z3.abs());
+        let max_x = p1.x.abs().max(p2.x.abs()).max(p3.x.abs());
+        let max_y = p1.y.abs().max(p2.y.abs()).max(p3.y.abs());
+        let delta_z = gamma(3) * max_z;
+        let delta_x = gamma(5) * (max_x + max_z);
+        let delta_y = gamma(5) * (max_y + max_z);
+        let delta_e = 2.0 * (gamma(2) * max_x * max_y + delta_y * max_x + delta_x * max_y);
+        let max_e = e1.abs().max(e2.abs()).max(e3.abs());
+        let delta_t =
+            3.0 * (gamma(3) * max_e * max_z + delta_e * max_z + delta_z * max_e) * inv_det.abs();
+        if t <= delta_t {
             return None;
         }
 
-        let t = f * edge2.dot(&q);
-        let point = p + (t * d);
+        let (q1, q2, q3) = (self.v1 * b1, self.v2 * b2, self.v3 * b3);
+        let edge1 = self.v2 - self.v1;
+        let edge2 = self.v3 - self.v1;
         let normal = edge1.cross(&edge2).unit();
-        let front_face = d.dot(&normal) < 0.0;
+        let front_face = ray.direction.dot(&normal) < 0.0;
         let normal = if front_face { normal } else { -normal };
 
         let hit_record = TrigHitRecord {
-            point,
+            point: q1 + q2 + q3,
             normal,
             front_face,
             t,
-            uv: (u, v),
+            uv: (b2, b3),
+            dpdu: edge1,
+            dpdv: edge2,
+            error: gamma(7) * (q1.abs() + q2.abs() + q3.abs()),
         };
         Some(hit_record)
     }
@@ -68,6 +146,8 @@ pub struct Mesh {
     center: Point,
     material: Box<dyn Material>,
     aabb: (Vec3, Vec3),
+    /// The area of each face plus those before it, for picking faces by area.
+    areas: Vec<f32>,
 }
 
 impl Mesh {
@@ -91,6 +171,16 @@ impl Mesh {
             z_max = z_max.max(v.z);
         }
 
+        let mut area = 0.0;
+        let areas = faces
+            .iter()
+            .map(|face| {
+                let trig = Self::trig(&vertices, face);
+                area += trig.area();
+                area
+            })
+            .collect();
+
         Self {
             vertices,
             faces,
@@ -100,6 +190,35 @@ impl Mesh {
                 Vec3::new(x_min, y_min, z_min),
                 Vec3::new(x_max, y_max, z_max),
             ),
+            areas,
+        }
+    }
+
+    fn trig(vertices: &[Point], face: &[u32; 3]) -> Trig {
+        Trig::new(
+            vertices[face[0] as usize],
+            vertices[face[1] as usize],
+            vertices[face[2] as usize],
+        )
+    }
+
+    fn record(&self, r: TrigHitRecord) -> HitRecord<'_> {
+        let point = r.point + self.center;
+        HitRecord {
+            point,
+            normal: r.normal,
+            front_face: r.front_face,
+            t: r.t,
+            uv: r.uv,
+            barycentric: Some(r.uv),
+            material: &*self.material,
+            dpdu: r.dpdu,
+            dpdv: r.dpdv,
+            dndu: Vec3::new(0.0, 0.0, 0.0),
+            dndv: Vec3::new(0.0, 0.0, 0.0),
+            footprint: None,
+            error: r.error + gamma(1) * point.abs(),
+            object: 0,
         }
     }
 
@@ -131,30 +250,31 @@ impl Mesh {
         let rmax = b - ray.origin;
         let d = ray.direction;
 
-        let t0 = f32::min(rmin.x / d.x, rmax.x / d.x);
-        let t1 = f32::max(rmin.x / d.x, rmax.x / d.x);
-        if f32::max(t0, t_min) >= f32::min(t1, t_max) {
-            return false;
-        }
-
-        let t0 = f32::min(rmin.y / d.y, rmax.y / d.y);
-        let t1 = f32::max(rmin.y / d.y, rmax.y / d.y);
-        if f32::max(t0, t_min) >= f32::min(t1, t_max) {
-            return false;
-        }
-
-        let t0 = f32::min(rmin.z / d.z, rmax.z / d.z);
-        let t1 = f32::max(rmin.z / d.z, rmax.z / d.z);
-        if f32::max(t0, t_min) >= f32::min(t1, t_max) {
-            return false;
+        // Far distances are pushed out by their rounding error, so rays that
+        // just touch the box (or cross a flat one) aren't missed.
+        let widen = 1.0 + 2.0 * gamma(3);
+        for axis in 0..3 {
+            let (near, far) = (
+                rmin.axis(axis) / d.axis(axis),
+                rmax.axis(axis) / d.axis(axis),
+            );
+            let t0 = f32::min(near, far);
+            let t1 = f32::max(near, far) * widen;
+            if f32::max(t0, t_min) > f32::min(t1, t_max) {
+                return false;
+            }
         }
 
-        return true;
+        true
     }
 }
 
 impl Hittable for Mesh {
-    fn hit(&self, ray: Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+    fn materials(&self) -> Vec<&dyn Material> {
+        vec![&*self.material]
+    }
+
+    fn hit(&self, ray: Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
         let mut hit_record: Option<TrigHitRecord> = None;
         let mut hit_t = t_max;
 
@@ -165,34 +285,33 @@ impl Hittable for Mesh {
         }
 
         for face in self.faces.iter() {
-            let trig = Trig::new(
-                self.vertices[face[0] as usize],
-                self.vertices[face[1] as usize],
-                self.vertices[face[2] as usize],
-            );
+            let trig = Self::trig(&self.vertices, face);
 
-            match trig.hit(transformed_ray) {
-                Some(record) => {
-                    if record.t < hit_t && record.t > t_min {
-                        hit_t = record.t;
-                        hit_record = Some(record);
-                    }
+            if let Some(record) = trig.hit(transformed_ray) {
+                if record.t < hit_t && record.t > t_min {
+                    hit_t = record.t;
+                    hit_record = Some(record);
                 }
-                None => (),
             }
         }
 
-        if let Some(r) = hit_record {
-            Some(HitRecord {
-                point: r.point + self.center,
-                normal: r.normal,
-                front_face: r.front_face,
-                t: r.t,
-                uv: r.uv,
-                material: &*self.material,
-            })
-        } else {
-            None
-        }
+        hit_record.map(|r| self.record(r))
+    }
+
+    fn area(&self) -> f32 {
+        self.areas.last().copied().unwrap_or(0.0)
+    }
+
+    fn sample_surface(&self, sampler: &mut dyn Sampler) -> Option<HitRecord<'_>> {
+        let target = sampler.next_1d() * self.area();
+        let index = self.areas.partition_point(|&area| area <= target);
+        let face = self.faces.get(index).or(self.faces.last())?;
+        let trig = Self::trig(&self.vertices, face);
+        Some(self.record(trig.sample(sampler)))
+    }
+
+    fn bounds(&self) -> Option<(Point, Point)> {
+        let (min, max) = self.aabb;
+        (!self.vertices.is_empty()).then_some((min + self.center, max + self.center))
     }
 }
@@ -1,16 +1,161 @@
-use super::{Material, Point, Ray, Vec3};
+use super::{texture::Footprint, Material, Point, Ray, Sampler, Vec3};
+use std::f32::consts::PI;
 
 pub trait Hittable: Send + Sync {
-    fn hit(&self, ray: Ray, t_min: f32, t_max: f32) -> Option<HitRecord>;
+    fn hit(&self, ray: Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>>;
+
+    /// The materials used by the object, used to number materials in ID passes.
+    fn materials(&self) -> Vec<&dyn Material> {
+        Vec::new()
+    }
+
+    /// Surface area, used to pick points on emissive objects. Objects without
+    /// an area are never used as starting points for light.
+    fn area(&self) -> f32 {
+        0.0
+    }
+
+    /// Picks a point uniformly over the surface, as a hit on its outside.
+    fn sample_surface(&self, _sampler: &mut dyn Sampler) -> Option<HitRecord<'_>> {
+        None
+    }
+
+    /// Corners of a box containing the object, used to fit path guiding to
+    /// the scene. Objects without bounds are left out.
+    fn bounds(&self) -> Option<(Point, Point)> {
+        None
+    }
 }
 
+#[derive(Clone, Copy)]
 pub struct HitRecord<'a> {
     pub point: Point,
     pub normal: Vec3,
     pub front_face: bool,
     pub t: f32,
     pub uv: (f32, f32),
+    /// Barycentric coordinates of the hit within a mesh triangle, for meshes.
+    pub barycentric: Option<(f32, f32)>,
     pub material: &'a dyn Material,
+    /// Derivatives of the point and normal with respect to `uv`.
+    pub dpdu: Vec3,
+    pub dpdv: Vec3,
+    pub dndu: Vec3,
+    pub dndv: Vec3,
+    /// Filled in by `compute_footprint` when the incoming ray carries differentials.
+    pub footprint: Option<Footprint>,
+    /// Bound on the floating point error in each coordinate of `point`.
+    pub error: Vec3,
+    /// Index of the hit object among the scene's objects, set by `Scene::hit`.
+    pub object: usize,
+}
+
+/// Fraction of a shadow ray's length left unchecked at its far end, so that it
+/// doesn't hit the surface it is aimed at.
+pub const SHADOW_EPSILON: f32 = 0.0001;
+
+/// Bound on the relative error of `n` floating point operations (Pharr et al.,
+/// "Physically Based Rendering", 3.9).
+pub(crate) fn gamma(n: u32) -> f32 {
+    let epsilon = f32::EPSILON / 2.0;
+    n as f32 * epsilon / (1.0 - n as f32 * epsilon)
+}
+
+impl HitRecord<'_> {
+    /// `point` moved off the surface along its normal, far enough to be past
+    /// its error, to the side that `direction` leaves on. Rays starting there
+    /// can't hit the surface again by rounding, whatever the scale of the scene.
+    pub fn offset_point(&self, direction: Vec3) -> Point {
+        let distance = self.normal.abs().dot(&self.error);
+        let mut offset = self.normal * distance;
+        if direction.dot(&self.normal) < 0.0 {
+            offset = -offset;
+        }
+
+        // Rounding the sum towards the offset keeps it from falling back.
+        let point = self.point + offset;
+        let round = |x: f32, offset: f32| {
+            if offset > 0.0 {
+                x.next_up()
+            } else if offset < 0.0 {
+                x.next_down()
+            } else {
+                x
+            }
+        };
+        Vec3::new(
+            round(point.x, offset.x),
+            round(point.y, offset.y),
+            round(point.z, offset.z),
+        )
+    }
+
+    /// A ray leaving the surface in `direction`, to be traced with a `t_min` of 0.
+    pub fn spawn_ray(&self, direction: Vec3) -> Ray {
+        Ray::new(self.offset_point(direction), direction)
+    }
+
+    /// A ray leaving the surface towards `target`, which it reaches at a `t`
+    /// of 1. Tracing it up to `1.0 - SHADOW_EPSILON` checks what lies between.
+    pub fn spawn_ray_to(&self, target: Point) -> Ray {
+        let origin = self.offset_point(target - self.point);
+        Ray::new(origin, target - origin)
+    }
+
+    /// Estimates the area of the surface covered by `ray` from its differentials.
+    pub fn compute_footprint(&mut self, ray: &Ray) {
+        let Some(d) = ray.differential else {
+            self.footprint = None;
+            return;
+        };
+
+        let n = self.normal;
+        let plane = n.dot(&self.point);
+        let (nx, ny) = (n.dot(&d.rx_direction), n.dot(&d.ry_direction));
+        if nx == 0.0 || ny == 0.0 {
+            self.footprint = None;
+            return;
+        }
+
+        let px = d.rx_origin + ((plane - n.dot(&d.rx_origin)) / nx) * d.rx_direction;
+        let py = d.ry_origin + ((plane - n.dot(&d.ry_origin)) / ny) * d.ry_direction;
+        let (dpdx, dpdy) = (px - self.point, py - self.point);
+
+        // Least squares solution of dpdx = dpdu * dudx + dpdv * dvdx (and likewise for y).
+        let (a, b, c) = (
+            self.dpdu.dot(&self.dpdu),
+            self.dpdu.dot(&self.dpdv),
+            self.dpdv.dot(&self.dpdv),
+        );
+        let det = a * c - b * b;
+        let solve = |dp: Vec3| {
+            if det.abs() < 1e-12 {
+                return (0.0, 0.0);
+            }
+            let (p, q) = (self.dpdu.dot(&dp), self.dpdv.dot(&dp));
+            ((c * p - b * q) / det, (a * q - b * p) / det)
+        };
+
+        let (dudx, dvdx) = solve(dpdx);
+        let (dudy, dvdy) = solve(dpdy);
+
+        self.footprint = Some(Footprint {
+            dpdx,
+            dpdy,
+            dudx,
+            dvdx,
+            dudy,
+            dvdy,
+        });
+    }
+
+    /// Derivatives of the normal with respect to screen space.
+    pub fn normal_derivatives(&self, footprint: &Footprint) -> (Vec3, Vec3) {
+        (
+            self.dndu * footprint.dudx + self.dndv * footprint.dvdx,
+            self.dndu * footprint.dudy + self.dndv * footprint.dvdy,
+        )
+    }
 }
 
 pub struct Sphere {
@@ -29,47 +174,97 @@ impl Sphere {
     }
 
     pub fn normal_at(&self, point: Point) -> Vec3 {
-        return (point - self.center) / self.radius;
+        (point - self.center) / self.radius
+    }
+
+    fn record(&self, point: Point, t: f32, front_face: bool) -> HitRecord<'_> {
+        // Projecting the point back onto the sphere bounds its error.
+        let local = point - self.center;
+        let local = local * (self.radius.abs() / local.len());
+        let point = self.center + local;
+        let error = gamma(5) * local.abs() + gamma(1) * point.abs();
+
+        let normal = self.normal_at(point);
+        let normal = if front_face { normal } else { -normal };
+        let uv = super::texture::uv_coords(local);
+
+        let rho = (local.x * local.x + local.z * local.z).sqrt().max(1e-6);
+        let dpdu = std::f32::consts::TAU * Vec3::new(local.z, 0.0, -local.x);
+        let dpdv = PI * Vec3::new(-local.x * local.y / rho, rho, -local.z * local.y / rho);
+        let sign = if front_face { 1.0 } else { -1.0 };
+
+        HitRecord {
+            point,
+            normal,
+            front_face,
+            t,
+            uv,
+            barycentric: None,
+            material: &*self.material,
+            dpdu,
+            dpdv,
+            dndu: dpdu * (sign / self.radius),
+            dndv: dpdv * (sign / self.radius),
+            footprint: None,
+            error,
+            object: 0,
+        }
     }
 }
 
 impl Hittable for Sphere {
-    fn hit(&self, ray: Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+    fn materials(&self) -> Vec<&dyn Material> {
+        vec![&*self.material]
+    }
+
+    fn hit(&self, ray: Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
         let oc = ray.origin - self.center;
         let a = ray.direction.dot(&ray.direction);
         let half_b = oc.dot(&ray.direction);
-        let c = oc.dot(&oc) - (self.radius * self.radius);
+        let radius_squared = self.radius * self.radius;
 
-        let d = (half_b * half_b) - (a * c);
+        // The discriminant from the closest point of the ray to the center,
+        // which loses less precision than `b² - ac` (Haines et al., "Precision
+        // Improvements for Ray/Sphere Intersection").
+        let closest = oc - ray.direction * (half_b / a);
+        let d = a * (radius_squared - closest.dot(&closest));
 
         if d < 0.0 {
             return None;
         }
 
-        let t = (-half_b - d.sqrt()) / a;
-        let t = if t < t_min || t > t_max {
-            let t = (-half_b + d.sqrt()) / a;
-            if t < t_min || t > t_max {
-                return None;
-            }
-            t
+        // Each root is found without subtracting nearly equal numbers.
+        let q = -(half_b + d.sqrt().copysign(half_b));
+        let c = oc.dot(&oc) - radius_squared;
+        let (t0, t1) = (q / a, c / q);
+        let (t0, t1) = if t0 <= t1 { (t0, t1) } else { (t1, t0) };
+
+        let t = if (t_min..=t_max).contains(&t0) {
+            t0
+        } else if (t_min..=t_max).contains(&t1) {
+            t1
         } else {
-            t
+            return None;
         };
 
         let point = ray.at(t);
-        let normal = self.normal_at(point);
-        let front_face = ray.direction.dot(&normal) < 0.0;
-        let normal = if front_face { normal } else { -normal };
-        let uv = super::texture::uv_coords(point - self.center);
+        let front_face = ray.direction.dot(&self.normal_at(point)) < 0.0;
+        Some(self.record(point, t, front_face))
+    }
 
-        Some(HitRecord {
-            point,
-            normal,
-            front_face,
-            t,
-            uv,
-            material: &*self.material,
-        })
+    fn area(&self) -> f32 {
+        4.0 * PI * self.radius * self.radius
+    }
+
+    fn sample_surface(&self, sampler: &mut dyn Sampler) -> Option<HitRecord<'_>> {
+        let direction = Vec3::random_unit_vector(sampler);
+        let point = self.center + direction * self.radius.abs();
+        Some(self.record(point, 0.0, true))
+    }
+
+    fn bounds(&self) -> Option<(Point, Point)> {
+        let radius = self.radius.abs();
+        let extent = Vec3::new(radius, radius, radius);
+        Some((self.center - extent, self.center + extent))
     }
 }
@@ -0,0 +1,286 @@
+use super::{Color, HdrImage};
+use std::fs::File;
+use std::io::{self, BufWriter};
+use std::path::Path;
+
+/// Additional images that can be produced alongside the beauty render.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pass {
+    /// Distance from the camera along its viewing direction.
+    Depth,
+    /// World space shading normal.
+    Normal,
+    /// Shading normal in camera space, with X right, Y up and Z towards the camera.
+    CameraNormal,
+    /// Color of the hit material, without lighting.
+    Albedo,
+    /// World space position.
+    Position,
+    /// Surface UV coordinates, stored in the first two channels.
+    Uv,
+    /// Index of the hit object in the scene, or -1 for the background.
+    ObjectId,
+    /// Index of the hit material among the scene's materials, or -1 for the background.
+    MaterialId,
+    /// Light emitted by surfaces seen directly from the camera.
+    Emission,
+    /// The background seen directly from the camera.
+    Environment,
+    /// Light reaching the camera after a single diffuse bounce.
+    DiffuseDirect,
+    /// Light reaching the camera after a diffuse first bounce, and more bounces after it.
+    DiffuseIndirect,
+    /// Light reaching the camera after a single glossy bounce.
+    GlossyDirect,
+    /// Light reaching the camera after a glossy first bounce, and more bounces after it.
+    GlossyIndirect,
+    /// Light reaching the camera after a single transmissive bounce.
+    TransmissionDirect,
+    /// Light reaching the camera after a transmissive first bounce, and more bounces after it.
+    TransmissionIndirect,
+    /// Number of samples taken for each pixel.
+    SampleCount,
+    /// Estimated standard error of each pixel's luminance, relative to the luminance.
+    Error,
+}
+
+impl Pass {
+    pub const ALL: [Pass; 18] = [
+        Pass::Depth,
+        Pass::Normal,
+        Pass::CameraNormal,
+        Pass::Albedo,
+        Pass::Position,
+        Pass::Uv,
+        Pass::ObjectId,
+        Pass::MaterialId,
+        Pass::Emission,
+        Pass::Environment,
+        Pass::DiffuseDirect,
+        Pass::DiffuseIndirect,
+        Pass::GlossyDirect,
+        Pass::GlossyIndirect,
+        Pass::TransmissionDirect,
+        Pass::TransmissionIndirect,
+        Pass::SampleCount,
+        Pass::Error,
+    ];
+
+    /// The layer name used for the pass in multi-layer files.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Pass::Depth => "depth",
+            Pass::Normal => "normal",
+            Pass::CameraNormal => "cameraNormal",
+            Pass::Albedo => "albedo",
+            Pass::Position => "position",
+            Pass::Uv => "uv",
+            Pass::ObjectId => "objectId",
+            Pass::MaterialId => "materialId",
+            Pass::Emission => "emission",
+            Pass::Environment => "environment",
+            Pass::DiffuseDirect => "diffuseDirect",
+            Pass::DiffuseIndirect => "diffuseIndirect",
+            Pass::GlossyDirect => "glossyDirect",
+            Pass::GlossyIndirect => "glossyIndirect",
+            Pass::TransmissionDirect => "transmissionDirect",
+            Pass::TransmissionIndirect => "transmissionIndirect",
+            Pass::SampleCount => "sampleCount",
+            Pass::Error => "error",
+        }
+    }
+
+    /// Names of the channels that carry data, in the order they are stored in an `HdrImage`.
+    pub fn channels(&self) -> &'static [&'static str] {
+        match self {
+            Pass::Depth => &["Z"],
+            Pass::Normal | Pass::CameraNormal | Pass::Position => &["X", "Y", "Z"],
+            Pass::Uv => &["U", "V"],
+            Pass::ObjectId | Pass::MaterialId => &["id"],
+            Pass::SampleCount => &["count"],
+            Pass::Error => &["error"],
+            _ => &["R", "G", "B"],
+        }
+    }
+
+    /// Whether the pass holds identifiers, which are taken from a single
+    /// sample instead of being averaged.
+    pub fn is_id(&self) -> bool {
+        matches!(self, Pass::ObjectId | Pass::MaterialId)
+    }
+
+    /// Whether the pass holds part of the beauty image. Together, these passes
+    /// sum to the beauty image.
+    pub fn is_lighting(&self) -> bool {
+        matches!(
+            self,
+            Pass::Emission
+                | Pass::Environment
+                | Pass::DiffuseDirect
+                | Pass::DiffuseIndirect
+                | Pass::GlossyDirect
+                | Pass::GlossyIndirect
+                | Pass::TransmissionDirect
+                | Pass::TransmissionIndirect
+        )
+    }
+}
+
+/// A set of passes, cheap to copy into `RenderOptions`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Passes(u32);
+
+impl Passes {
+    pub const NONE: Passes = Passes(0);
+
+    pub const fn with(self, pass: Pass) -> Self {
+        Passes(self.0 | 1 << pass as u32)
+    }
+
+    pub const fn contains(self, pass: Pass) -> bool {
+        self.0 & (1 << pass as u32) != 0
+    }
+
+    pub const fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn iter(self) -> impl Iterator<Item = Pass> {
+        Pass::ALL.into_iter().filter(move |p| self.contains(*p))
+    }
+}
+
+/// The beauty image of a render, along with any requested passes.
+#[derive(Debug, Clone)]
+pub struct RenderResult {
+    pub beauty: HdrImage,
+    pub passes: Vec<(Pass, HdrImage)>,
+}
+
+impl RenderResult {
+    /// Creates black images for the beauty render and each pass.
+    pub fn new(width: usize, height: usize, passes: Passes) -> Self {
+        Self {
+            beauty: HdrImage::new(width, height),
+            passes: passes
+                .iter()
+                .map(|p| (p, HdrImage::new(width, height)))
+                .collect(),
+        }
+    }
+
+    pub fn pass(&self, pass: Pass) -> Option<&HdrImage> {
+        self.passes.iter().find(|(p, _)| *p == pass).map(|(_, i)| i)
+    }
+
+    pub fn pass_mut(&mut self, pass: Pass) -> Option<&mut HdrImage> {
+        self.passes
+            .iter_mut()
+            .find(|(p, _)| *p == pass)
+            .map(|(_, i)| i)
+    }
+
+    /// Copies another result into this one, with its top left corner at `(x, y)`.
+    pub fn blit(&mut self, other: &RenderResult, x: usize, y: usize) {
+        self.beauty.blit(&other.beauty, x, y);
+        for (pass, image) in other.passes.iter() {
+            if let Some(target) = self.pass_mut(*pass) {
+                target.blit(image, x, y);
+            }
+        }
+    }
+
+    /// Merges `other`, rendered with `other_samples` samples per pixel, into
+    /// this result, rendered with `samples` samples per pixel.
+    ///
+    /// Each pass is averaged the way a single render would average it:
+    /// identifiers are kept from this result, geometric passes are weighted by
+    /// the samples that hit something, and everything else by all samples.
+    pub fn accumulate(&mut self, other: &RenderResult, samples: usize, other_samples: usize) {
+        let (width, height) = (self.beauty.width(), self.beauty.height());
+        let (n, m) = (samples as f32, other_samples as f32);
+
+        for y in 0..height {
+            for x in 0..width {
+                let (alpha, other_alpha) =
+                    (self.beauty.get_alpha(x, y), other.beauty.get_alpha(x, y));
+                let (hits, other_hits) = (alpha * n, other_alpha * m);
+
+                for (pass, image) in self.passes.iter_mut() {
+                    let Some(other_image) = other.pass(*pass) else {
+                        continue;
+                    };
+                    let (a, b) = match pass {
+                        _ if pass.is_id() => continue,
+                        Pass::SampleCount => {
+                            let count = image.get_pixel(x, y) + other_image.get_pixel(x, y);
+                            image.set_pixel(x, y, count);
+                            continue;
+                        }
+                        Pass::Error => {
+                            // Combines the variances of both means, assuming they are similar.
+                            let (e, f) = (image.get_pixel(x, y).x, other_image.get_pixel(x, y).x);
+                            let error = ((e * n).powi(2) + (f * m).powi(2)).sqrt() / (n + m);
+                            image.set_pixel(x, y, Color::new(error, error, error));
+                            continue;
+                        }
+                        _ if pass.is_lighting() => (n, m),
+                        Pass::Albedo => (n, m),
+                        _ => (hits, other_hits),
+                    };
+                    if a + b > 0.0 {
+                        let color =
+                            (image.get_pixel(x, y) * a + other_image.get_pixel(x, y) * b) / (a + b);
+                        image.set_pixel(x, y, color);
+                    }
+                }
+
+                let color =
+                    (self.beauty.get_pixel(x, y) * n + other.beauty.get_pixel(x, y) * m) / (n + m);
+                self.beauty.set_pixel(x, y, color);
+                self.beauty.set_alpha(x, y, (hits + other_hits) / (n + m));
+            }
+        }
+    }
+
+    /// Saves the beauty image and every pass as layers of a single OpenEXR file.
+    pub fn save_exr(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let (width, height) = (self.beauty.width(), self.beauty.height());
+
+        let mut channels: Vec<(String, Vec<f32>)> = vec![
+            ("R".to_string(), channel(&self.beauty, |c, _| c.x)),
+            ("G".to_string(), channel(&self.beauty, |c, _| c.y)),
+            ("B".to_string(), channel(&self.beauty, |c, _| c.z)),
+            ("A".to_string(), channel(&self.beauty, |_, a| a)),
+        ];
+
+        for (pass, image) in self.passes.iter() {
+            let components = [
+                channel(image, |c, _| c.x),
+                channel(image, |c, _| c.y),
+                channel(image, |c, _| c.z),
+            ];
+            for (name, values) in pass.channels().iter().zip(components) {
+                channels.push((format!("{}.{}", pass.name(), name), values));
+            }
+        }
+
+        let channels: Vec<(&str, &[f32])> = channels
+            .iter()
+            .map(|(name, values)| (name.as_str(), values.as_slice()))
+            .collect();
+
+        let writer = BufWriter::new(File::create(path)?);
+        crate::io::exr::write(writer, width, height, &channels)
+    }
+}
+
+fn channel(image: &HdrImage, f: impl Fn(Color, f32) -> f32) -> Vec<f32> {
+    let mut values = Vec::with_capacity(image.width() * image.height());
+    for y in 0..image.height() {
+        for x in 0..image.width() {
+            values.push(f(image.get_pixel(x, y), image.get_alpha(x, y)));
+        }
+    }
+    values
+}
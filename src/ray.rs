@@ -1,17 +1,114 @@
-use super::{Point, Vec3};
+use super::{HitRecord, Point, Vec3};
 
 #[derive(Debug, Clone, Copy)]
 pub struct Ray {
     pub origin: Point,
     pub direction: Vec3,
+    pub differential: Option<Differential>,
+}
+
+/// Offset rays one pixel to the right (`rx`) and one pixel down (`ry`),
+/// used to estimate the footprint of a ray on the surfaces it hits.
+#[derive(Debug, Clone, Copy)]
+pub struct Differential {
+    pub rx_origin: Point,
+    pub rx_direction: Vec3,
+    pub ry_origin: Point,
+    pub ry_direction: Vec3,
 }
 
 impl Ray {
     pub const fn new(origin: Point, direction: Vec3) -> Self {
-        Self { origin, direction }
+        Self {
+            origin,
+            direction,
+            differential: None,
+        }
+    }
+
+    pub const fn with_differential(
+        origin: Point,
+        direction: Vec3,
+        differential: Differential,
+    ) -> Self {
+        Self {
+            origin,
+            direction,
+            differential: Some(differential),
+        }
     }
 
     pub fn at(&self, t: f32) -> Point {
         self.origin + t * self.direction
     }
+
+    /// Scales the offset rays towards the main ray, e.g. to account for
+    /// several samples being taken per pixel.
+    pub fn scale_differential(&mut self, scale: f32) {
+        if let Some(d) = self.differential.as_mut() {
+            d.rx_origin = self.origin + (d.rx_origin - self.origin) * scale;
+            d.ry_origin = self.origin + (d.ry_origin - self.origin) * scale;
+            d.rx_direction = self.direction + (d.rx_direction - self.direction) * scale;
+            d.ry_direction = self.direction + (d.ry_direction - self.direction) * scale;
+        }
+    }
+}
+
+impl Differential {
+    /// Differentials of a ray specularly reflected at `hit_record` into `direction`.
+    pub fn reflect(ray: &Ray, hit_record: &HitRecord, direction: Vec3) -> Option<Differential> {
+        let d = ray.differential?;
+        let footprint = hit_record.footprint?;
+
+        let n = hit_record.normal;
+        let wo = -ray.direction.unit();
+        let wi = direction.unit();
+        let (dndx, dndy) = hit_record.normal_derivatives(&footprint);
+
+        let dwodx = -d.rx_direction.unit() - wo;
+        let dwody = -d.ry_direction.unit() - wo;
+        let ddndx = dwodx.dot(&n) + wo.dot(&dndx);
+        let ddndy = dwody.dot(&n) + wo.dot(&dndy);
+
+        Some(Differential {
+            rx_origin: hit_record.point + footprint.dpdx,
+            rx_direction: wi - dwodx + 2.0 * (wo.dot(&n) * dndx + ddndx * n),
+            ry_origin: hit_record.point + footprint.dpdy,
+            ry_direction: wi - dwody + 2.0 * (wo.dot(&n) * dndy + ddndy * n),
+        })
+    }
+
+    /// Differentials of a ray refracted at `hit_record` into `direction`, where
+    /// `eta` is the ratio of refractive indices on the incident and transmitted side.
+    pub fn refract(
+        ray: &Ray,
+        hit_record: &HitRecord,
+        direction: Vec3,
+        eta: f32,
+    ) -> Option<Differential> {
+        let d = ray.differential?;
+        let footprint = hit_record.footprint?;
+
+        let n = hit_record.normal;
+        let wo = -ray.direction.unit();
+        let wi = direction.unit();
+        let (dndx, dndy) = hit_record.normal_derivatives(&footprint);
+
+        let dwodx = -d.rx_direction.unit() - wo;
+        let dwody = -d.ry_direction.unit() - wo;
+        let ddndx = dwodx.dot(&n) + wo.dot(&dndx);
+        let ddndy = dwody.dot(&n) + wo.dot(&dndy);
+
+        let cos_i = wo.dot(&n);
+        let cos_t = wi.dot(&n).abs();
+        let mu = eta * cos_i - cos_t;
+        let dmu = eta - (eta * eta * cos_i) / cos_t;
+
+        Some(Differential {
+            rx_origin: hit_record.point + footprint.dpdx,
+            rx_direction: wi - eta * dwodx + (dmu * ddndx) * n + mu * dndx,
+            ry_origin: hit_record.point + footprint.dpdy,
+            ry_direction: wi - eta * dwody + (dmu * ddndy) * n + mu * dndy,
+        })
+    }
 }
@@ -0,0 +1,105 @@
+//! Sources of sample values for the camera, materials and light sampling.
+
+mod halton;
+mod independent;
+mod sobol;
+mod stratified;
+
+pub use halton::Halton;
+pub use independent::Independent;
+pub use sobol::Sobol;
+pub use stratified::Stratified;
+
+use super::rng;
+
+/// Supplies the random numbers used to render a sample of a pixel.
+///
+/// Each sample is a point in a high dimensional space; samplers spread the
+/// samples of a pixel over that space more evenly than independent random
+/// numbers would, so renders converge faster.
+pub trait Sampler {
+    /// Starts the `index`th sample of pixel `(x, y)`, restarting at the first dimension.
+    fn start_sample(&mut self, x: usize, y: usize, index: usize);
+
+    /// The next dimension of the current sample, in `[0, 1)`.
+    fn next_1d(&mut self) -> f32;
+
+    /// The next two dimensions of the current sample, in `[0, 1)`.
+    fn next_2d(&mut self) -> (f32, f32);
+
+    /// Continues the current sample from the start of stream `stream`, a
+    /// separate run of its dimensions, so that how many dimensions one part
+    /// of a path uses doesn't shift those of the next. Only samplers that
+    /// keep values from sample to sample, like Metropolis chains, need this.
+    fn start_stream(&mut self, _stream: usize) {}
+}
+
+/// The samplers that can be selected in `RenderOptions`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SamplerKind {
+    /// Independent uniform random numbers.
+    #[default]
+    Independent,
+    /// Jittered samples, one per stratum of each dimension.
+    Stratified,
+    /// The Halton sequence, randomized per pixel.
+    Halton,
+    /// The Sobol sequence with Owen scrambling.
+    Sobol,
+}
+
+impl SamplerKind {
+    /// Creates a sampler for renders taking `samples` samples per pixel.
+    pub fn build(self, seed: u64, samples: usize) -> Box<dyn Sampler> {
+        match self {
+            SamplerKind::Independent => Box::new(Independent::new(seed)),
+            SamplerKind::Stratified => Box::new(Stratified::new(seed, samples)),
+            SamplerKind::Halton => Box::new(Halton::new(seed)),
+            SamplerKind::Sobol => Box::new(Sobol::new(seed)),
+        }
+    }
+}
+
+/// A seed unique to a pixel, dimension and render seed.
+fn hash(seed: u64, x: usize, y: usize, dimension: usize) -> u64 {
+    let pixel = rng::mix(x as u64 ^ rng::mix(y as u64));
+    rng::mix(seed ^ rng::mix(pixel ^ rng::mix(dimension as u64)))
+}
+
+/// The `i`th element of a random permutation of `0..l` chosen by `p`,
+/// without storing the permutation.
+///
+/// From Kensler, "Correlated Multi-Jittered Sampling".
+fn permutation_element(mut i: u32, l: u32, p: u32) -> u32 {
+    let mut w = l - 1;
+    w |= w >> 1;
+    w |= w >> 2;
+    w |= w >> 4;
+    w |= w >> 8;
+    w |= w >> 16;
+
+    loop {
+        i ^= p;
+        i = i.wrapping_mul(0xe170_893d);
+        i ^= p >> 16;
+        i ^= (i & w) >> 4;
+        i ^= p >> 8;
+        i = i.wrapping_mul(0x0929_eb3f);
+        i ^= p >> 23;
+        i ^= (i & w) >> 1;
+        i = i.wrapping_mul(1 | p >> 27);
+        i = i.wrapping_mul(0x6935_fa69);
+        i ^= (i & w) >> 11;
+        i = i.wrapping_mul(0x74dc_b303);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0x9e50_1cc3);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0xc860_a3df);
+        i &= w;
+        i ^= i >> 5;
+        if i < l {
+            break;
+        }
+    }
+    (i.wrapping_add(p)) % l
+}
@@ -0,0 +1,81 @@
+use super::Sampler;
+use crate::{rng, Rng};
+
+const PRIMES: [u32; 32] = [
+    2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89, 97,
+    101, 103, 107, 109, 113, 127, 131,
+];
+
+/// The Halton sequence, using the `n`th prime as the base of the `n`th dimension.
+///
+/// Digits are Owen scrambled differently for every pixel, which keeps
+/// neighbouring pixels from sharing the same pattern, and spreads out the
+/// first few samples of dimensions with large bases. Dimensions past the
+/// supported primes fall back to independent random numbers.
+pub struct Halton {
+    seed: u64,
+    pixel: (usize, usize),
+    index: usize,
+    dimension: usize,
+    rng: Rng,
+}
+
+impl Halton {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            pixel: (0, 0),
+            index: 0,
+            dimension: 0,
+            rng: Rng::new(seed, 0),
+        }
+    }
+}
+
+impl Sampler for Halton {
+    fn start_sample(&mut self, x: usize, y: usize, index: usize) {
+        self.pixel = (x, y);
+        self.index = index;
+        self.dimension = 0;
+        self.rng = Rng::for_sample(self.seed, super::hash(0, x, y, 0), index as u64);
+    }
+
+    fn next_1d(&mut self) -> f32 {
+        let dimension = self.dimension;
+        self.dimension += 1;
+
+        let Some(&base) = PRIMES.get(dimension) else {
+            return self.rng.uniform();
+        };
+
+        let (x, y) = self.pixel;
+        let seed = super::hash(self.seed, x, y, dimension);
+        scrambled_radical_inverse(base, self.index as u64, seed)
+    }
+
+    fn next_2d(&mut self) -> (f32, f32) {
+        (self.next_1d(), self.next_1d())
+    }
+}
+
+/// Mirrors the digits of `index` in `base` around the radix point, permuting
+/// each digit depending on the digits below it.
+///
+/// From Pharr et al., "Physically Based Rendering", 4th edition.
+fn scrambled_radical_inverse(base: u32, mut index: u64, seed: u64) -> f32 {
+    // Enough digits to fill the precision of an `f32`.
+    let digits = (24.0 / (base as f32).log2()).ceil() as usize;
+
+    let inverse_base = 1.0 / base as f64;
+    let (mut reversed, mut scale) = (0u64, 1.0);
+    for _ in 0..digits {
+        let digit = (index % base as u64) as u32;
+        index /= base as u64;
+
+        let digit_seed = rng::mix(seed ^ reversed) as u32;
+        let digit = super::permutation_element(digit, base, digit_seed);
+        reversed = reversed * base as u64 + digit as u64;
+        scale *= inverse_base;
+    }
+    ((reversed as f64 * scale) as f32).min(1.0 - f32::EPSILON / 2.0)
+}
@@ -0,0 +1,31 @@
+use super::Sampler;
+use crate::Rng;
+
+/// Independent uniform random numbers.
+pub struct Independent {
+    seed: u64,
+    rng: Rng,
+}
+
+impl Independent {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            rng: Rng::new(seed, 0),
+        }
+    }
+}
+
+impl Sampler for Independent {
+    fn start_sample(&mut self, x: usize, y: usize, index: usize) {
+        self.rng = Rng::for_sample(self.seed, super::hash(0, x, y, 0), index as u64);
+    }
+
+    fn next_1d(&mut self) -> f32 {
+        self.rng.uniform()
+    }
+
+    fn next_2d(&mut self) -> (f32, f32) {
+        (self.rng.uniform(), self.rng.uniform())
+    }
+}
@@ -0,0 +1,92 @@
+use super::Sampler;
+
+/// The first two dimensions of the Sobol sequence, with Owen scrambling.
+///
+/// Following Burley, "Practical Hash-based Owen Scrambling", every pair of
+/// dimensions reuses the same two Sobol dimensions, with the sample order
+/// shuffled and the values scrambled differently for each pair, so that
+/// pairs aren't correlated.
+pub struct Sobol {
+    seed: u64,
+    pixel: (usize, usize),
+    index: usize,
+    dimension: usize,
+}
+
+impl Sobol {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            pixel: (0, 0),
+            index: 0,
+            dimension: 0,
+        }
+    }
+}
+
+impl Sampler for Sobol {
+    fn start_sample(&mut self, x: usize, y: usize, index: usize) {
+        self.pixel = (x, y);
+        self.index = index;
+        self.dimension = 0;
+    }
+
+    fn next_1d(&mut self) -> f32 {
+        self.next_2d().0
+    }
+
+    fn next_2d(&mut self) -> (f32, f32) {
+        let (x, y) = self.pixel;
+        let seed = super::hash(self.seed, x, y, self.dimension) as u32;
+        self.dimension += 1;
+
+        let index = nested_uniform_scramble(self.index as u32, seed);
+        let u = nested_uniform_scramble(sobol(index, 0), hash_combine(seed, 0));
+        let v = nested_uniform_scramble(sobol(index, 1), hash_combine(seed, 1));
+        (to_float(u), to_float(v))
+    }
+}
+
+/// Dimension 0 or 1 of the Sobol sequence, as a 32-bit fraction.
+fn sobol(mut index: u32, dimension: usize) -> u32 {
+    if dimension == 0 {
+        return index.reverse_bits();
+    }
+
+    let mut result = 0;
+    let mut direction = 1 << 31;
+    while index > 0 {
+        if index & 1 == 1 {
+            result ^= direction;
+        }
+        index >>= 1;
+        direction ^= direction >> 1;
+    }
+    result
+}
+
+/// Owen scrambling, which randomly flips digits of `x` depending on the digits above them.
+fn nested_uniform_scramble(x: u32, seed: u32) -> u32 {
+    laine_karras_permutation(x.reverse_bits(), seed).reverse_bits()
+}
+
+/// A hash in which each bit only depends on the bits below it.
+fn laine_karras_permutation(mut x: u32, seed: u32) -> u32 {
+    x = x.wrapping_add(seed);
+    x ^= x.wrapping_mul(0x6c50_b47c);
+    x ^= x.wrapping_mul(0xb82f_1e52);
+    x ^= x.wrapping_mul(0xc7af_e638);
+    x ^= x.wrapping_mul(0x8d22_f6e6);
+    x
+}
+
+fn hash_combine(seed: u32, value: u32) -> u32 {
+    seed ^ (value
+        .wrapping_add(0x9e37_79b9)
+        .wrapping_add(seed << 6)
+        .wrapping_add(seed >> 2))
+}
+
+fn to_float(x: u32) -> f32 {
+    (x >> 8) as f32 / (1 << 24) as f32
+}
@@ -0,0 +1,69 @@
+use super::Sampler;
+use crate::Rng;
+
+/// Jittered stratified samples.
+///
+/// Each dimension is split into as many strata as there are samples per
+/// pixel (or a grid of them, for pairs of dimensions), and every sample
+/// falls in a different stratum. Strata are shuffled independently for each
+/// dimension so that dimensions aren't correlated.
+pub struct Stratified {
+    seed: u64,
+    samples: usize,
+    /// Strata along each axis of a pair of dimensions.
+    grid: (usize, usize),
+    pixel: (usize, usize),
+    index: usize,
+    dimension: usize,
+    rng: Rng,
+}
+
+impl Stratified {
+    pub fn new(seed: u64, samples: usize) -> Self {
+        let samples = samples.max(1);
+        let columns = (samples as f32).sqrt() as usize;
+        let grid = (columns, samples.div_ceil(columns));
+
+        Self {
+            seed,
+            samples,
+            grid,
+            pixel: (0, 0),
+            index: 0,
+            dimension: 0,
+            rng: Rng::new(seed, 0),
+        }
+    }
+
+    /// The shuffled stratum of the current sample, out of `count`.
+    fn stratum(&mut self, count: usize) -> usize {
+        let (x, y) = self.pixel;
+        let seed = super::hash(self.seed, x, y, self.dimension);
+        self.dimension += 1;
+        super::permutation_element((self.index % count) as u32, count as u32, seed as u32) as usize
+    }
+}
+
+impl Sampler for Stratified {
+    fn start_sample(&mut self, x: usize, y: usize, index: usize) {
+        self.pixel = (x, y);
+        self.index = index;
+        self.dimension = 0;
+        self.rng = Rng::for_sample(self.seed, super::hash(0, x, y, 0), index as u64);
+    }
+
+    fn next_1d(&mut self) -> f32 {
+        let stratum = self.stratum(self.samples);
+        (stratum as f32 + self.rng.uniform()) / self.samples as f32
+    }
+
+    fn next_2d(&mut self) -> (f32, f32) {
+        let (columns, rows) = self.grid;
+        let stratum = self.stratum(columns * rows);
+        let (column, row) = (stratum % columns, stratum / columns);
+        (
+            (column as f32 + self.rng.uniform()) / columns as f32,
+            (row as f32 + self.rng.uniform()) / rows as f32,
+        )
+    }
+}
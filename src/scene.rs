@@ -1,5 +1,21 @@
-use super::{random, Camera, Color, HitRecord, Hittable, Image, Pixel, Point, Ray, Texture};
-use std::sync::mpsc;
+use super::filter::{Film, Splats};
+use super::firefly::PixelSamples;
+use super::guiding::{self, Guide, Recorded};
+use super::integrator::{Contribution, PathTracer, Splat};
+use super::job::{JobState, RenderJob};
+use super::material::Lobe;
+use super::rng;
+use super::tile::tiles;
+use super::{
+    Accumulation, Background, Camera, Clamp, Color, Denoiser, Filter, HitRecord, Hittable,
+    Integrator, IntegratorKind, Material, Pass, Passes, PathGuiding, Ray, RenderResult, Sampler,
+    SamplerKind, TileOrder,
+};
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::{Duration, Instant};
 
 #[derive(Debug, Clone, Copy)]
 pub struct RenderOptions {
@@ -8,145 +24,635 @@ pub struct RenderOptions {
     pub crop_region: ((usize, usize), (usize, usize)),
     pub samples: usize,
     pub bounces: usize,
+    /// Seed for all random sampling. Renders with the same seed are identical,
+    /// however they are split between threads.
+    pub seed: u64,
+    /// How sample values are generated.
+    pub sampler: SamplerKind,
+    /// The light transport algorithm used for each camera ray.
+    pub integrator: IntegratorKind,
     pub clip_start: f32,
     pub clip_end: f32,
     pub block_size: usize,
+    /// Number of render threads, or `None` to use one per available core.
+    pub threads: Option<usize>,
+    /// The order in which blocks are rendered.
+    pub tile_order: TileOrder,
+    pub passes: Passes,
+    /// Denoises the beauty image once the whole region has been rendered.
+    pub denoise: Option<Denoiser>,
+    /// Stops sampling pixels early once they are converged. `samples` is
+    /// then the maximum number of samples per pixel.
+    pub adaptive: Option<AdaptiveSampling>,
+    /// The reconstruction filter used for the beauty image and lighting passes.
+    pub filter: Filter,
+    /// Guides path tracing towards where light was found in earlier passes.
+    /// Only `progressive_render` has earlier passes to learn from.
+    pub guiding: Option<PathGuiding>,
+    /// Limits how bright a single sample's light can be, to keep rare bright
+    /// paths from showing up as fireflies.
+    pub clamp: Option<Clamp>,
+    /// How each pixel's samples are combined into its value.
+    pub accumulation: Accumulation,
+}
+
+impl RenderOptions {
+    /// Number of threads to render with.
+    pub(crate) fn thread_count(&self) -> usize {
+        match self.threads {
+            Some(threads) => threads,
+            None => thread::available_parallelism().map_or(1, |n| n.get()),
+        }
+    }
+}
+
+/// Settings for adaptive sampling.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AdaptiveSampling {
+    /// Samples every pixel receives before its error is checked. At least 2 are taken.
+    pub min_samples: usize,
+    /// Target standard error of a pixel's luminance, relative to the luminance
+    /// itself (or to 0.01, for darker pixels).
+    pub threshold: f32,
+    /// Once this much time has passed since the render started, pixels stop
+    /// sampling as soon as they reach `min_samples`.
+    pub time_budget: Option<Duration>,
 }
 
 pub struct Scene {
     camera: Camera,
-    texture: Box<dyn Texture>,
+    background: Box<dyn Background>,
     objects: Vec<Box<dyn Hittable>>,
 }
 
 impl Scene {
     pub const fn new(
         camera: Camera,
-        texture: Box<dyn Texture>,
+        background: Box<dyn Background>,
         objects: Vec<Box<dyn Hittable>>,
     ) -> Self {
         Self {
             camera,
-            texture,
+            background,
             objects,
         }
     }
 
-    pub fn ray_color(&self, ray: Ray, t_min: f32, t_max: f32, bounces: usize) -> Color {
-        if bounces == 0 {
-            return Color::new(0.0, 0.0, 0.0);
-        }
-
-        let record = self.hit(ray, t_min, t_max);
-
-        if record.is_some() {
-            let record = record.unwrap();
+    pub fn ray_color(
+        &self,
+        ray: Ray,
+        t_min: f32,
+        t_max: f32,
+        bounces: usize,
+        sampler: &mut dyn Sampler,
+    ) -> Color {
+        let hit = self.hit(ray, t_min, t_max);
+        PathTracer::new(bounces, t_max)
+            .radiance(self, ray, hit, sampler, &mut Vec::new())
+            .total()
+    }
 
-            let (u, v) = record.uv;
-            let point = record.point.clone();
-            let material = record.material;
+    pub fn camera(&self) -> &Camera {
+        &self.camera
+    }
 
-            let scatter = material.scatter(ray, record);
-            if scatter.is_none() {
-                material.emit(u, v, point)
-            } else {
-                let (scattered, color1) = scatter.unwrap();
-                let color2 = self.ray_color(scattered, t_min, t_max, bounces - 1);
+    pub fn background(&self) -> &dyn Background {
+        &*self.background
+    }
 
-                Color::new(
-                    color1.x * color2.x,
-                    color1.y * color2.y,
-                    color1.z * color2.z,
-                )
-            }
-        } else {
-            let (u, v) = super::texture::uv_coords(ray.direction);
-            let p = Point::new(f32::INFINITY, f32::INFINITY, f32::INFINITY);
-            self.texture.color(u, v, p)
-        }
+    pub(crate) fn objects(&self) -> &[Box<dyn Hittable>] {
+        &self.objects
     }
 
-    pub fn hit(&self, ray: Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+    pub fn hit(&self, ray: Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
         let mut hit_record: Option<HitRecord> = None;
         let mut hit_t = t_max;
 
-        for object in self.objects.iter() {
-            match object.hit(ray, t_min, t_max) {
-                Some(record) => {
-                    if record.t < hit_t && record.t > t_min {
-                        hit_t = record.t;
-                        hit_record = Some(record);
-                    }
+        for (index, object) in self.objects.iter().enumerate() {
+            if let Some(record) = object.hit(ray, t_min, t_max) {
+                if record.t < hit_t && record.t > t_min {
+                    hit_t = record.t;
+                    hit_record = Some(HitRecord {
+                        object: index,
+                        ..record
+                    });
                 }
-                None => (),
             }
         }
 
         hit_record
     }
 
-    pub fn render(&self, options: RenderOptions) -> Image {
+    /// Renders the crop region into a linear image, with alpha holding the
+    /// fraction of samples that hit an object, along with any requested passes.
+    pub fn render(&self, options: RenderOptions) -> RenderResult {
+        let ((x_start, _), (y_start, _)) = options.crop_region;
+        let options_with_features = RenderOptions {
+            passes: feature_passes(options),
+            ..options
+        };
+
+        let job = JobState::new(options);
+        let integrator = options.integrator.build(self, options);
+        let materials = MaterialIds::new(self);
+        let (mut result, mut film, splats) =
+            self.render_block(options_with_features, &*integrator, &materials, &job);
+        film.add_splats(&splats);
+        film.develop(
+            &mut result,
+            x_start,
+            y_start,
+            &lighting_passes(options.passes),
+            splat_scale(options, &job),
+        );
+        finish(result, options)
+    }
+
+    /// Renders the crop region, returning every pass except the beauty image
+    /// and lighting passes, which are splatted into a film reaching past the
+    /// region by the filter's margin instead, along with the splats the
+    /// integrator found for the whole image.
+    ///
+    /// Stops early, leaving the remaining pixels empty, if `job` is cancelled.
+    fn render_block(
+        &self,
+        options: RenderOptions,
+        integrator: &dyn Integrator,
+        materials: &MaterialIds,
+        job: &JobState,
+    ) -> (RenderResult, Film, Splats) {
         let ((x_start, x_end), (y_start, y_end)) = options.crop_region;
 
-        let mut image = Image::new(x_end - x_start, y_end - y_start);
+        let mut result = RenderResult::new(x_end - x_start, y_end - y_start, options.passes);
+        let passes: Vec<Pass> = options.passes.iter().filter(|p| !p.is_lighting()).collect();
+        let lighting = lighting_passes(options.passes);
 
-        for y in y_start..y_end {
+        let margin = options.filter.margin();
+        let mut film = Film::new(
+            x_start as isize - margin as isize,
+            y_start as isize - margin as isize,
+            x_end - x_start + 2 * margin,
+            y_end - y_start + 2 * margin,
+            1 + lighting.len(),
+        );
+        let mut values = vec![Color::new(0.0, 0.0, 0.0); 1 + lighting.len()];
+        let mut splats = Splats::new(1 + lighting.len());
+        let mut sample_splats: Vec<Splat> = Vec::new();
+        let mut pixel_samples = PixelSamples::new(1 + lighting.len());
+        // Splats reach a pixel scaled by its share of the image.
+        let splat_scale = (options.width * options.height) as f32;
+
+        let (dx, dy) = (1.0 / options.width as f32, 1.0 / options.height as f32);
+        let differential_scale = f32::max(0.125, 1.0 / (options.samples as f32).sqrt());
+        let mut sampler = options.sampler.build(options.seed, options.samples);
+        let mut pass_sums = vec![Color::new(0.0, 0.0, 0.0); passes.len()];
+        let adaptive = options
+            .adaptive
+            .filter(|_| options.integrator.supports_adaptive());
+
+        'rows: for y in y_start..y_end {
             for x in x_start..x_end {
-                let mut color_sum = Color::new(0.0, 0.0, 0.0);
-                for _ in 0..options.samples {
-                    let frac_x = (x as f32 + random(0.0, 1.0)) / (options.width as f32);
-                    let frac_y = (y as f32 + random(0.0, 1.0)) / (options.height as f32);
-
-                    let ray = self.camera.ray(frac_x, frac_y);
-                    let color =
-                        self.ray_color(ray, options.clip_start, options.clip_end, options.bounces);
-                    color_sum = color_sum + color;
+                if job.is_cancelled() {
+                    break 'rows;
+                }
+
+                pass_sums.fill(Color::new(0.0, 0.0, 0.0));
+                let mut hits = 0;
+                let mut error = Welford::default();
+                let mut samples = 0;
+                for sample in 0..options.samples {
+                    sampler.start_sample(x, y, sample);
+
+                    let (jitter_x, jitter_y) = sampler.next_2d();
+                    let (sample_x, sample_y) = (x as f32 + jitter_x, y as f32 + jitter_y);
+                    let frac_x = sample_x / (options.width as f32);
+                    let frac_y = sample_y / (options.height as f32);
+
+                    let mut ray =
+                        self.camera
+                            .ray_differential(frac_x, frac_y, dx, dy, &mut *sampler);
+                    ray.scale_differential(differential_scale);
+
+                    let hit = self.hit(ray, options.clip_start, options.clip_end);
+
+                    let mut contribution =
+                        integrator.radiance(self, ray, hit, &mut *sampler, &mut sample_splats);
+                    if let Some(clamp) = options.clamp {
+                        contribution = clamp.apply(contribution, 1.0);
+                    }
+                    error.add(contribution.total().luminance());
+                    samples += 1;
+
+                    if hit.is_some() {
+                        hits += 1;
+                    }
+
+                    let lobe = hit.map(|record| record.material.lobe());
+                    values[0] = contribution.total();
+                    for (pass, value) in lighting.iter().zip(values[1..].iter_mut()) {
+                        *value = lighting_value(*pass, lobe, contribution);
+                    }
+                    let alpha = hit.is_some() as u8 as f32;
+                    match options.accumulation {
+                        Accumulation::Mean => {
+                            film.add_sample(&options.filter, sample_x, sample_y, &values, alpha)
+                        }
+                        _ => pixel_samples.push(sample_x, sample_y, &values, alpha),
+                    }
+
+                    for splat in sample_splats.drain(..) {
+                        let contribution = match options.clamp {
+                            Some(clamp) => clamp.apply(splat.contribution, splat_scale),
+                            None => splat.contribution,
+                        };
+                        values[0] = contribution.total();
+                        for (pass, value) in lighting.iter().zip(values[1..].iter_mut()) {
+                            *value = lighting_value(*pass, splat.lobe, contribution);
+                        }
+                        splats.add(splat.x, splat.y, &values);
+                    }
+
+                    for (pass, sum) in passes.iter().zip(pass_sums.iter_mut()) {
+                        // Identifiers can't be averaged, so only the first sample is used.
+                        if pass.is_id() && sample > 0 {
+                            continue;
+                        }
+                        *sum = *sum + self.pass_value(*pass, ray, hit, materials);
+                    }
+
+                    if let Some(adaptive) = adaptive {
+                        let timed_out = job.deadline.is_some_and(|d| Instant::now() >= d);
+                        let converged = error.relative_error() < adaptive.threshold;
+                        if samples >= adaptive.min_samples.max(2) && (converged || timed_out) {
+                            break;
+                        }
+                    }
+                }
+
+                pixel_samples.flush(&mut film, &options.filter, options.accumulation);
+                job.samples.fetch_add(samples, Ordering::Relaxed);
+                job.completed_pixels.fetch_add(1, Ordering::Relaxed);
+
+                let (px, py) = (x - x_start, y - y_start);
+                for (pass, sum) in passes.iter().zip(&pass_sums) {
+                    // Geometric passes are averaged over the samples that hit something.
+                    let count = match pass {
+                        _ if pass.is_id() => 1,
+                        Pass::Albedo => samples,
+                        _ => hits.max(1),
+                    };
+                    let value = match pass {
+                        Pass::SampleCount => Color::new(1.0, 1.0, 1.0) * samples as f32,
+                        Pass::Error => Color::new(1.0, 1.0, 1.0) * error.relative_error(),
+                        _ => *sum / count as f32,
+                    };
+                    let image = result.pass_mut(*pass).unwrap();
+                    image.set_pixel(px, py, value);
                 }
-                color_sum = color_sum / (options.samples as f32);
-                image.set_pixel(x - x_start, y - y_start, Pixel::from_color(color_sum));
             }
         }
-        image
+        (result, film, splats)
     }
 
-    pub fn threaded_render(&self, options: RenderOptions) -> Image {
+    fn pass_value(
+        &self,
+        pass: Pass,
+        ray: Ray,
+        hit: Option<HitRecord>,
+        materials: &MaterialIds,
+    ) -> Color {
+        let Some(mut record) = hit else {
+            return match pass {
+                Pass::ObjectId | Pass::MaterialId => Color::new(-1.0, -1.0, -1.0),
+                _ => Color::new(0.0, 0.0, 0.0),
+            };
+        };
+
+        let (u, v) = record.uv;
+        let id = |id: f32| Color::new(id, id, id);
+
+        match pass {
+            Pass::Depth => id(self.camera.depth(record.point)),
+            Pass::Normal => record.normal,
+            Pass::CameraNormal => self.camera.to_camera_space(record.normal),
+            Pass::Albedo => {
+                record.compute_footprint(&ray);
+                record.material.albedo(&record)
+            }
+            Pass::Position => record.point,
+            Pass::Uv => Color::new(u, v, 0.0),
+            Pass::ObjectId => id(record.object as f32),
+            Pass::MaterialId => match materials.id(self, &record) {
+                Some(index) => id(index as f32),
+                None => id(-1.0),
+            },
+            _ => Color::new(0.0, 0.0, 0.0),
+        }
+    }
+
+    pub fn threaded_render(&self, options: RenderOptions) -> RenderResult {
+        self.render_job(options, &JobState::new(options), None)
+    }
+
+    /// Starts rendering on background threads, returning a handle to follow
+    /// the render's progress, cancel it, and wait for the result.
+    pub fn spawn_render(self: Arc<Self>, options: RenderOptions) -> RenderJob {
+        let state = Arc::new(JobState::new(options));
+        let handle = thread::spawn({
+            let state = state.clone();
+            move || self.render_job(options, &state, None)
+        });
+        RenderJob::new(state, handle)
+    }
+
+    fn render_job(
+        &self,
+        options: RenderOptions,
+        job: &JobState,
+        guide: Option<Arc<Guide>>,
+    ) -> RenderResult {
         let ((x_start, x_end), (y_start, y_end)) = options.crop_region;
-        let (tx, rx) = mpsc::channel();
 
-        let mut num_blocks = 0;
-        std::thread::scope(|s| {
-            for x in (x_start..x_end).step_by(options.block_size) {
-                for y in (y_start..y_end).step_by(options.block_size) {
-                    num_blocks += 1;
+        let tiles = tiles(options.crop_region, options.block_size, options.tile_order);
+        let next_tile = AtomicUsize::new(0);
+        let threads = options.thread_count();
+        let integrator = &*options
+            .integrator
+            .build_guided(self, options, guide.clone());
+        let materials = &MaterialIds::new(self);
+
+        let (width, height) = (x_end - x_start, y_end - y_start);
+        let mut final_result = RenderResult::new(width, height, feature_passes(options));
+        let lighting = lighting_passes(options.passes);
+        let mut final_film = Film::new(
+            x_start as isize,
+            y_start as isize,
+            width,
+            height,
+            1 + lighting.len(),
+        );
+
+        thread::scope(|s| {
+            let (tx, rx) = mpsc::channel();
+
+            for _ in 0..threads.clamp(1, tiles.len().max(1)) {
+                let (tx, tiles, next_tile) = (tx.clone(), &tiles, &next_tile);
+                s.spawn(move || loop {
+                    let index = next_tile.fetch_add(1, Ordering::Relaxed);
+                    let Some(&region) = tiles.get(index) else {
+                        break;
+                    };
+                    if job.is_cancelled() {
+                        break;
+                    }
                     let options = RenderOptions {
-                        crop_region: ((x, x + options.block_size), (y, y + options.block_size)),
+                        crop_region: region,
+                        passes: feature_passes(options),
                         ..options
                     };
-                    let tx = tx.clone();
-                    s.spawn(move || {
-                        let img = self.render(options);
-                        tx.send((x, y, img)).unwrap();
-                    });
+                    let (result, film, splats) =
+                        self.render_block(options, integrator, materials, job);
+                    let recorded = guiding::take_recorded();
+                    if !job.is_cancelled() {
+                        job.completed_tiles.fetch_add(1, Ordering::Relaxed);
+                    }
+                    tx.send((index, (result, film, splats, recorded))).unwrap();
+                });
+            }
+            drop(tx);
+
+            // Films overlap by the filter's margin, so samples near block edges
+            // also reach pixels in neighbouring blocks.
+            let mut merge = |index: usize, (result, film, splats, recorded): Block| {
+                let ((x_offset, _), (y_offset, _)) = tiles[index];
+                final_result.blit(&result, x_offset - x_start, y_offset - y_start);
+                final_film.merge(&film);
+                final_film.add_splats(&splats);
+                if let Some(guide) = &guide {
+                    guide.add(&recorded);
                 }
+            };
+
+            // Blocks finish in whatever order the threads get to them, but are
+            // merged in tile order so that the sums don't depend on timing.
+            let mut pending = BTreeMap::new();
+            let mut next = 0;
+            for (index, block) in rx {
+                pending.insert(index, block);
+                while let Some(block) = pending.remove(&next) {
+                    merge(next, block);
+                    next += 1;
+                }
+            }
+            // Blocks after one skipped by cancelling are still merged.
+            for (index, block) in pending {
+                merge(index, block);
             }
         });
 
-        let mut final_img = Image::new(x_end - x_start, y_end - y_start);
+        let scale = splat_scale(options, job);
+        final_film.develop(&mut final_result, x_start, y_start, &lighting, scale);
+        finish(final_result, options)
+    }
 
-        for (x_offset, y_offset, img) in rx.iter().take(num_blocks) {
-            for x in 0..options.block_size {
-                if x + x_offset >= x_end {
-                    break;
-                }
-                for y in 0..options.block_size {
-                    if y + y_offset >= y_end {
-                        break;
-                    }
-                    let pixel = img.get_pixel(x, y);
-                    final_img.set_pixel(x_offset + x, y_offset + y, pixel);
-                }
+    /// Renders `samples_per_pass` samples per pixel (at least 1) at a time until
+    /// `options.samples` is reached, calling `callback` with the running
+    /// estimate after each pass. Stops early if `callback` returns `false`.
+    ///
+    /// Denoising, if enabled, is only applied to the returned result, and
+    /// adaptive sampling is not supported. Path guiding, if enabled, learns
+    /// from each pass to guide the next. `Accumulation::MedianOfMeans` only
+    /// groups the samples of a pass, so it has no effect with fewer than 2
+    /// samples per pass.
+    pub fn progressive_render(
+        &self,
+        options: RenderOptions,
+        samples_per_pass: usize,
+        mut callback: impl FnMut(Progress) -> bool,
+    ) -> RenderResult {
+        // Passes without samples would never reach `options.samples`.
+        let samples_per_pass = samples_per_pass.max(1);
+
+        // Each pass needs different samples, so it gets its own seed.
+        let pass_options = |samples, pass: usize| RenderOptions {
+            samples,
+            seed: rng::mix(options.seed ^ rng::mix(pass as u64)),
+            integrator: options.integrator.for_pass(pass),
+            passes: feature_passes(options),
+            denoise: None,
+            adaptive: None,
+            ..options
+        };
+
+        let mut guide = options
+            .guiding
+            .map(|guiding| Arc::new(Guide::new(self, guiding)));
+        let mut render_pass = |options| {
+            let result = self.render_job(options, &JobState::new(options), guide.clone());
+            guide = guide.as_ref().map(|guide| Arc::new(guide.refined()));
+            result
+        };
+
+        let mut samples = samples_per_pass.min(options.samples);
+        let mut result = render_pass(pass_options(samples, 0));
+
+        for pass in 1.. {
+            let progress = Progress {
+                pass,
+                samples,
+                total_samples: options.samples,
+                result: &result,
+            };
+            if !callback(progress) || samples >= options.samples {
+                break;
             }
+
+            let pass_samples = samples_per_pass.min(options.samples - samples);
+            let pass_result = render_pass(pass_options(pass_samples, pass));
+            result.accumulate(&pass_result, samples, pass_samples);
+            samples += pass_samples;
         }
-        final_img
+
+        finish(result, options)
+    }
+}
+
+/// The state of a progressive render after a pass.
+#[derive(Debug, Clone, Copy)]
+pub struct Progress<'a> {
+    /// Number of passes rendered so far, starting at 1.
+    pub pass: usize,
+    /// Samples per pixel accumulated so far.
+    pub samples: usize,
+    pub total_samples: usize,
+    /// The current estimate, without denoising.
+    pub result: &'a RenderResult,
+}
+
+/// A rendered block, with the light it recorded for path guiding, waiting to
+/// be merged into the whole image.
+type Block = (RenderResult, Film, Splats, Vec<Recorded>);
+
+/// Running mean and variance of a pixel's samples, using Welford's algorithm.
+#[derive(Debug, Clone, Copy, Default)]
+struct Welford {
+    count: usize,
+    mean: f32,
+    m2: f32,
+}
+
+impl Welford {
+    fn add(&mut self, value: f32) {
+        self.count += 1;
+        let delta = value - self.mean;
+        self.mean += delta / self.count as f32;
+        self.m2 += delta * (value - self.mean);
+    }
+
+    /// Standard error of the mean, relative to the mean.
+    fn relative_error(&self) -> f32 {
+        if self.count < 2 {
+            return f32::INFINITY;
+        }
+        let variance = self.m2 / (self.count - 1) as f32;
+        (variance / self.count as f32).sqrt() / self.mean.max(0.01)
+    }
+}
+
+/// The lighting passes in `passes`, which are filtered along with the beauty image.
+fn lighting_passes(passes: Passes) -> Vec<Pass> {
+    passes.iter().filter(|p| p.is_lighting()).collect()
+}
+
+/// The passes to render for `options`, including those the denoiser needs.
+fn feature_passes(options: RenderOptions) -> Passes {
+    match options.denoise {
+        Some(_) => options
+            .passes
+            .with(Pass::Albedo)
+            .with(Pass::Normal)
+            .with(Pass::Depth),
+        None => options.passes,
+    }
+}
+
+/// The factor splats are multiplied by, which spreads them over every pixel
+/// of the image and averages them over the samples taken, each of which
+/// could splat anywhere.
+fn splat_scale(options: RenderOptions, job: &JobState) -> f64 {
+    let samples = job.samples.load(Ordering::Relaxed).max(1);
+    (options.width * options.height) as f64 / samples as f64
+}
+
+/// Denoises a finished render if requested, dropping any passes that were
+/// only rendered for the denoiser.
+fn finish(mut result: RenderResult, options: RenderOptions) -> RenderResult {
+    if let Some(denoiser) = options.denoise {
+        if let Some(beauty) = denoiser.denoise_result(&result) {
+            result.beauty = beauty;
+        }
+        result
+            .passes
+            .retain(|(pass, _)| options.passes.contains(*pass));
+    }
+    result
+}
+
+/// Numbers the materials of a scene for material ID passes, in order of
+/// appearance. Each object owns its materials, so a material is identified by
+/// the object's index and its place among the object's materials.
+pub(crate) struct MaterialIds {
+    /// ID of the first material of each object, and one past the last object's.
+    offsets: Vec<usize>,
+}
+
+impl MaterialIds {
+    pub fn new(scene: &Scene) -> Self {
+        let mut offsets = vec![0];
+        for object in scene.objects() {
+            offsets.push(offsets[offsets.len() - 1] + object.materials().len());
+        }
+        Self { offsets }
+    }
+
+    /// The ID of the material at `record`, or `None` if its object doesn't list it.
+    pub fn id(&self, scene: &Scene, record: &HitRecord) -> Option<usize> {
+        let (first, end) = (self.offsets[record.object], self.offsets[record.object + 1]);
+        let slot = match end - first {
+            0 => return None,
+            1 => 0,
+            _ => {
+                let address = record.material as *const dyn Material as *const ();
+                scene.objects()[record.object]
+                    .materials()
+                    .iter()
+                    .position(|m| *m as *const dyn Material as *const () == address)?
+            }
+        };
+        Some(first + slot)
+    }
+}
+
+/// The part of a sample's contribution that belongs to a lighting pass,
+/// split by the kind of scattering at the first hit, if there was one.
+fn lighting_value(pass: Pass, lobe: Option<Lobe>, contribution: Contribution) -> Color {
+    let black = Color::new(0.0, 0.0, 0.0);
+
+    let Some(lobe) = lobe else {
+        return match pass {
+            Pass::Environment => contribution.emitted,
+            _ => black,
+        };
+    };
+
+    match pass {
+        Pass::Emission => contribution.emitted,
+        Pass::DiffuseDirect if lobe == Lobe::Diffuse => contribution.direct,
+        Pass::DiffuseIndirect if lobe == Lobe::Diffuse => contribution.indirect,
+        Pass::GlossyDirect if lobe == Lobe::Glossy => contribution.direct,
+        Pass::GlossyIndirect if lobe == Lobe::Glossy => contribution.indirect,
+        Pass::TransmissionDirect if lobe == Lobe::Transmission => contribution.direct,
+        Pass::TransmissionIndirect if lobe == Lobe::Transmission => contribution.indirect,
+        _ => black,
     }
 }
@@ -1,7 +1,63 @@
-use super::{Color, Point};
+mod combine;
+mod image;
+mod projection;
+mod transform;
+
+pub use combine::{Add, ColorRamp, Invert, Mix, Multiply, Remap};
+pub use image::{ImageTexture, MipFilter};
+pub use projection::{Decal, Projection, Projector};
+pub use transform::{UvTransform, WorldTransform};
+
+use super::{Color, HitRecord, Point, Vec3};
 
 pub trait Texture: Send + Sync {
     fn color(&self, u: f32, v: f32, p: Point) -> Color;
+
+    /// Returns the texture averaged over `footprint`.
+    ///
+    /// Textures prone to aliasing should override this; by default the
+    /// footprint is ignored.
+    fn color_filtered(&self, u: f32, v: f32, p: Point, _footprint: &Footprint) -> Color {
+        self.color(u, v, p)
+    }
+
+    /// Looks up the texture at a hit, filtering it when the footprint is known.
+    fn color_at(&self, hit_record: &HitRecord) -> Color {
+        let (u, v) = hit_record.uv;
+        lookup(self, u, v, hit_record.point, hit_record.footprint.as_ref())
+    }
+}
+
+/// The screen space derivatives of a hit point and its UV coordinates,
+/// describing the area of the surface covered by one pixel.
+#[derive(Debug, Clone, Copy)]
+pub struct Footprint {
+    pub dpdx: Vec3,
+    pub dpdy: Vec3,
+    pub dudx: f32,
+    pub dvdx: f32,
+    pub dudy: f32,
+    pub dvdy: f32,
+}
+
+impl Footprint {
+    /// The larger of the two world space extents of the footprint.
+    pub fn width(&self) -> f32 {
+        f32::max(self.dpdx.len(), self.dpdy.len())
+    }
+}
+
+fn lookup<T: Texture + ?Sized>(
+    texture: &T,
+    u: f32,
+    v: f32,
+    p: Point,
+    footprint: Option<&Footprint>,
+) -> Color {
+    match footprint {
+        Some(footprint) => texture.color_filtered(u, v, p, footprint),
+        None => texture.color(u, v, p),
+    }
 }
 
 pub struct Sky;
@@ -56,11 +112,21 @@ impl Checkered {
 }
 
 impl Texture for Checkered {
-    fn color(&self, u: f32, v: f32, p: Point) -> Color {
+    fn color(&self, _u: f32, _v: f32, p: Point) -> Color {
         if (p.x * self.size).sin() * (p.y * self.size).sin() * (p.z * self.size).sin() < 0.0 {
             self.color1
         } else {
             self.color2
         }
     }
+
+    /// Fades to the average of both colors as the footprint approaches the size of a check.
+    fn color_filtered(&self, u: f32, v: f32, p: Point, footprint: &Footprint) -> Color {
+        let checks = footprint.width() * self.size / std::f32::consts::PI;
+        let t = (checks - 0.5).clamp(0.0, 1.0);
+        let t = t * t * (3.0 - 2.0 * t);
+
+        let average = (self.color1 + self.color2) / 2.0;
+        self.color(u, v, p) * (1.0 - t) + average * t
+    }
 }
@@ -0,0 +1,193 @@
+use super::{lookup, Footprint, Texture};
+use crate::{Color, Point};
+
+/// Blends between two textures, using `factor` as a per-channel weight.
+pub struct Mix {
+    a: Box<dyn Texture>,
+    b: Box<dyn Texture>,
+    factor: Box<dyn Texture>,
+}
+
+impl Mix {
+    pub fn new(a: Box<dyn Texture>, b: Box<dyn Texture>, factor: Box<dyn Texture>) -> Self {
+        Self { a, b, factor }
+    }
+
+    fn eval(&self, u: f32, v: f32, p: Point, footprint: Option<&Footprint>) -> Color {
+        let f = lookup(&*self.factor, u, v, p, footprint);
+        let a = lookup(&*self.a, u, v, p, footprint);
+        let b = lookup(&*self.b, u, v, p, footprint);
+        a * (Color::new(1.0, 1.0, 1.0) - f) + b * f
+    }
+}
+
+impl Texture for Mix {
+    fn color(&self, u: f32, v: f32, p: Point) -> Color {
+        self.eval(u, v, p, None)
+    }
+
+    fn color_filtered(&self, u: f32, v: f32, p: Point, footprint: &Footprint) -> Color {
+        self.eval(u, v, p, Some(footprint))
+    }
+}
+
+pub struct Add {
+    a: Box<dyn Texture>,
+    b: Box<dyn Texture>,
+}
+
+impl Add {
+    pub fn new(a: Box<dyn Texture>, b: Box<dyn Texture>) -> Self {
+        Self { a, b }
+    }
+
+    fn eval(&self, u: f32, v: f32, p: Point, footprint: Option<&Footprint>) -> Color {
+        lookup(&*self.a, u, v, p, footprint) + lookup(&*self.b, u, v, p, footprint)
+    }
+}
+
+impl Texture for Add {
+    fn color(&self, u: f32, v: f32, p: Point) -> Color {
+        self.eval(u, v, p, None)
+    }
+
+    fn color_filtered(&self, u: f32, v: f32, p: Point, footprint: &Footprint) -> Color {
+        self.eval(u, v, p, Some(footprint))
+    }
+}
+
+pub struct Multiply {
+    a: Box<dyn Texture>,
+    b: Box<dyn Texture>,
+}
+
+impl Multiply {
+    pub fn new(a: Box<dyn Texture>, b: Box<dyn Texture>) -> Self {
+        Self { a, b }
+    }
+
+    fn eval(&self, u: f32, v: f32, p: Point, footprint: Option<&Footprint>) -> Color {
+        lookup(&*self.a, u, v, p, footprint) * lookup(&*self.b, u, v, p, footprint)
+    }
+}
+
+impl Texture for Multiply {
+    fn color(&self, u: f32, v: f32, p: Point) -> Color {
+        self.eval(u, v, p, None)
+    }
+
+    fn color_filtered(&self, u: f32, v: f32, p: Point, footprint: &Footprint) -> Color {
+        self.eval(u, v, p, Some(footprint))
+    }
+}
+
+pub struct Invert {
+    texture: Box<dyn Texture>,
+}
+
+impl Invert {
+    pub fn new(texture: Box<dyn Texture>) -> Self {
+        Self { texture }
+    }
+
+    fn eval(&self, u: f32, v: f32, p: Point, footprint: Option<&Footprint>) -> Color {
+        Color::new(1.0, 1.0, 1.0) - lookup(&*self.texture, u, v, p, footprint)
+    }
+}
+
+impl Texture for Invert {
+    fn color(&self, u: f32, v: f32, p: Point) -> Color {
+        self.eval(u, v, p, None)
+    }
+
+    fn color_filtered(&self, u: f32, v: f32, p: Point, footprint: &Footprint) -> Color {
+        self.eval(u, v, p, Some(footprint))
+    }
+}
+
+/// Linearly maps each channel from `from` to `to`, optionally clamping the result.
+pub struct Remap {
+    texture: Box<dyn Texture>,
+    from: (f32, f32),
+    to: (f32, f32),
+    clamp: bool,
+}
+
+impl Remap {
+    pub fn new(texture: Box<dyn Texture>, from: (f32, f32), to: (f32, f32), clamp: bool) -> Self {
+        Self {
+            texture,
+            from,
+            to,
+            clamp,
+        }
+    }
+
+    fn remap(&self, x: f32) -> f32 {
+        let (from_min, from_max) = self.from;
+        let (to_min, to_max) = self.to;
+
+        let t = (x - from_min) / (from_max - from_min);
+        let t = if self.clamp { t.clamp(0.0, 1.0) } else { t };
+        to_min + t * (to_max - to_min)
+    }
+
+    fn eval(&self, u: f32, v: f32, p: Point, footprint: Option<&Footprint>) -> Color {
+        let c = lookup(&*self.texture, u, v, p, footprint);
+        Color::new(self.remap(c.x), self.remap(c.y), self.remap(c.z))
+    }
+}
+
+impl Texture for Remap {
+    fn color(&self, u: f32, v: f32, p: Point) -> Color {
+        self.eval(u, v, p, None)
+    }
+
+    fn color_filtered(&self, u: f32, v: f32, p: Point, footprint: &Footprint) -> Color {
+        self.eval(u, v, p, Some(footprint))
+    }
+}
+
+/// Maps the luminance of a texture through a piecewise linear gradient.
+pub struct ColorRamp {
+    texture: Box<dyn Texture>,
+    stops: Vec<(f32, Color)>,
+}
+
+impl ColorRamp {
+    /// `stops` are `(position, color)` pairs; they are sorted by position.
+    pub fn new(texture: Box<dyn Texture>, mut stops: Vec<(f32, Color)>) -> Self {
+        assert!(!stops.is_empty());
+        stops.sort_by(|a, b| a.0.total_cmp(&b.0));
+        Self { texture, stops }
+    }
+
+    fn eval(&self, u: f32, v: f32, p: Point, footprint: Option<&Footprint>) -> Color {
+        let x = lookup(&*self.texture, u, v, p, footprint).luminance();
+
+        let first = self.stops[0];
+        if x <= first.0 {
+            return first.1;
+        }
+
+        for pair in self.stops.windows(2) {
+            let ((x0, c0), (x1, c1)) = (pair[0], pair[1]);
+            if x <= x1 {
+                let t = if x1 > x0 { (x - x0) / (x1 - x0) } else { 1.0 };
+                return c0 * (1.0 - t) + c1 * t;
+            }
+        }
+
+        self.stops[self.stops.len() - 1].1
+    }
+}
+
+impl Texture for ColorRamp {
+    fn color(&self, u: f32, v: f32, p: Point) -> Color {
+        self.eval(u, v, p, None)
+    }
+
+    fn color_filtered(&self, u: f32, v: f32, p: Point, footprint: &Footprint) -> Color {
+        self.eval(u, v, p, Some(footprint))
+    }
+}
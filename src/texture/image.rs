@@ -0,0 +1,196 @@
+use super::{Footprint, Texture};
+use crate::{Color, HdrImage, Point};
+
+const MAX_ANISOTROPY: f32 = 8.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MipFilter {
+    /// Bilinear lookup in the full resolution image, ignoring the footprint.
+    Bilinear,
+    /// Bilinear lookups in the two mipmap levels closest to the footprint size, blended together.
+    Trilinear,
+    /// Elliptically weighted average over the anisotropic footprint.
+    Ewa,
+}
+
+/// A texture backed by an image, wrapped around the UV square and mipmapped for filtering.
+pub struct ImageTexture {
+    levels: Vec<HdrImage>,
+    filter: MipFilter,
+}
+
+impl ImageTexture {
+    pub fn new(image: HdrImage, filter: MipFilter) -> Self {
+        let mut levels = vec![image];
+
+        loop {
+            let last = &levels[levels.len() - 1];
+            let (width, height) = (last.width(), last.height());
+            if width <= 1 && height <= 1 {
+                break;
+            }
+
+            let (w, h) = ((width / 2).max(1), (height / 2).max(1));
+            let mut next = HdrImage::new(w, h);
+            for y in 0..h {
+                for x in 0..w {
+                    let (x0, y0) = (2 * x, 2 * y);
+                    let (x1, y1) = ((x0 + 1).min(width - 1), (y0 + 1).min(height - 1));
+                    let sum = last.get_pixel(x0, y0)
+                        + last.get_pixel(x1, y0)
+                        + last.get_pixel(x0, y1)
+                        + last.get_pixel(x1, y1);
+                    next.set_pixel(x, y, sum / 4.0);
+                }
+            }
+            levels.push(next);
+        }
+
+        Self { levels, filter }
+    }
+
+    fn texel(&self, level: usize, x: i64, y: i64) -> Color {
+        let image = &self.levels[level];
+        let (w, h) = (image.width() as i64, image.height() as i64);
+        image.get_pixel(x.rem_euclid(w) as usize, y.rem_euclid(h) as usize)
+    }
+
+    /// `v` runs from the bottom of the image, while image rows run from the top.
+    fn to_texel_space(&self, level: usize, u: f32, v: f32) -> (f32, f32) {
+        let image = &self.levels[level];
+        (
+            u * image.width() as f32 - 0.5,
+            (1.0 - v) * image.height() as f32 - 0.5,
+        )
+    }
+
+    fn bilinear(&self, level: usize, u: f32, v: f32) -> Color {
+        let level = level.min(self.levels.len() - 1);
+        let (x, y) = self.to_texel_space(level, u, v);
+        let (x0, y0) = (x.floor(), y.floor());
+        let (dx, dy) = (x - x0, y - y0);
+        let (x0, y0) = (x0 as i64, y0 as i64);
+
+        self.texel(level, x0, y0) * ((1.0 - dx) * (1.0 - dy))
+            + self.texel(level, x0 + 1, y0) * (dx * (1.0 - dy))
+            + self.texel(level, x0, y0 + 1) * ((1.0 - dx) * dy)
+            + self.texel(level, x0 + 1, y0 + 1) * (dx * dy)
+    }
+
+    fn trilinear(&self, u: f32, v: f32, footprint: &Footprint) -> Color {
+        let width = 2.0
+            * [
+                footprint.dudx,
+                footprint.dvdx,
+                footprint.dudy,
+                footprint.dvdy,
+            ]
+            .iter()
+            .fold(0.0f32, |m, d| m.max(d.abs()));
+
+        self.blend_levels(u, v, width, |level| self.bilinear(level, u, v))
+    }
+
+    fn ewa(&self, u: f32, v: f32, footprint: &Footprint) -> Color {
+        let (mut major, mut minor) = (
+            (footprint.dudx, footprint.dvdx),
+            (footprint.dudy, footprint.dvdy),
+        );
+        let length = |(a, b): (f32, f32)| (a * a + b * b).sqrt();
+        if length(major) < length(minor) {
+            std::mem::swap(&mut major, &mut minor);
+        }
+
+        let (major_length, mut minor_length) = (length(major), length(minor));
+        if minor_length * MAX_ANISOTROPY < major_length && minor_length > 0.0 {
+            let scale = major_length / (minor_length * MAX_ANISOTROPY);
+            minor = (minor.0 * scale, minor.1 * scale);
+            minor_length *= scale;
+        }
+
+        if minor_length == 0.0 {
+            return self.bilinear(0, u, v);
+        }
+
+        self.blend_levels(u, v, minor_length, |level| {
+            self.ewa_level(level, u, v, major, minor)
+        })
+    }
+
+    /// Picks the mipmap levels matching a filter `width` in UV space and blends lookups in them.
+    fn blend_levels(&self, u: f32, v: f32, width: f32, lookup: impl Fn(usize) -> Color) -> Color {
+        let last = self.levels.len() - 1;
+        let level = last as f32 + width.max(1e-8).log2();
+
+        if level <= 0.0 {
+            self.bilinear(0, u, v)
+        } else if level >= last as f32 {
+            self.texel(last, 0, 0)
+        } else {
+            let lower = level.floor() as usize;
+            let t = level - lower as f32;
+            lookup(lower) * (1.0 - t) + lookup(lower + 1) * t
+        }
+    }
+
+    fn ewa_level(&self, level: usize, u: f32, v: f32, d0: (f32, f32), d1: (f32, f32)) -> Color {
+        if level >= self.levels.len() {
+            return self.texel(self.levels.len() - 1, 0, 0);
+        }
+
+        let image = &self.levels[level];
+        let (w, h) = (image.width() as f32, image.height() as f32);
+        let (s, t) = self.to_texel_space(level, u, v);
+        let (d0, d1) = ((d0.0 * w, -d0.1 * h), (d1.0 * w, -d1.1 * h));
+
+        // Coefficients of the implicit ellipse A s² + B s t + C t² = 1.
+        let a = d0.1 * d0.1 + d1.1 * d1.1 + 1.0;
+        let b = -2.0 * (d0.0 * d0.1 + d1.0 * d1.1);
+        let c = d0.0 * d0.0 + d1.0 * d1.0 + 1.0;
+        let inv_f = 1.0 / (a * c - b * b * 0.25);
+        let (a, b, c) = (a * inv_f, b * inv_f, c * inv_f);
+
+        let det = -b * b + 4.0 * a * c;
+        let inv_det = 1.0 / det;
+        let (u_sqrt, v_sqrt) = ((det * c).sqrt(), (a * det).sqrt());
+        let s0 = (s - 2.0 * inv_det * u_sqrt).ceil() as i64;
+        let s1 = (s + 2.0 * inv_det * u_sqrt).floor() as i64;
+        let t0 = (t - 2.0 * inv_det * v_sqrt).ceil() as i64;
+        let t1 = (t + 2.0 * inv_det * v_sqrt).floor() as i64;
+
+        let mut sum = Color::new(0.0, 0.0, 0.0);
+        let mut weights = 0.0;
+        for it in t0..=t1 {
+            let tt = it as f32 - t;
+            for is in s0..=s1 {
+                let ss = is as f32 - s;
+                let r2 = a * ss * ss + b * ss * tt + c * tt * tt;
+                if r2 < 1.0 {
+                    let weight = (-2.0 * r2).exp() - (-2.0f32).exp();
+                    sum = sum + self.texel(level, is, it) * weight;
+                    weights += weight;
+                }
+            }
+        }
+
+        if weights > 0.0 {
+            sum / weights
+        } else {
+            self.bilinear(level, u, v)
+        }
+    }
+}
+
+impl Texture for ImageTexture {
+    fn color(&self, u: f32, v: f32, _p: Point) -> Color {
+        self.bilinear(0, u, v)
+    }
+
+    fn color_filtered(&self, u: f32, v: f32, _p: Point, footprint: &Footprint) -> Color {
+        match self.filter {
+            MipFilter::Bilinear => self.bilinear(0, u, v),
+            MipFilter::Trilinear => self.trilinear(u, v, footprint),
+            MipFilter::Ewa => self.ewa(u, v, footprint),
+        }
+    }
+}
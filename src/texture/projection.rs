@@ -0,0 +1,181 @@
+use super::{lookup, Footprint, Texture};
+use crate::{Color, Point, Vec3};
+
+#[derive(Debug, Clone, Copy)]
+pub enum Projector {
+    /// Parallel projection through a `width` by `height` rectangle.
+    Planar { width: f32, height: f32 },
+    /// Equirectangular projection of all directions around the projector.
+    Spherical,
+    /// Perspective projection with a vertical field of view in degrees.
+    Perspective { fov: f32, aspect_ratio: f32 },
+}
+
+/// Maps a texture onto the scene through a projector placed in world space,
+/// ignoring the UV coordinates of the surface.
+///
+/// Points outside the projected area see black.
+pub struct Projection {
+    texture: Box<dyn Texture>,
+    projector: Projector,
+    position: Point,
+    u: Vec3,
+    v: Vec3,
+    w: Vec3,
+}
+
+impl Projection {
+    /// The projector sits at `position` and points towards `target`, with `up`
+    /// orienting the image, like a `Camera`.
+    pub fn new(
+        texture: Box<dyn Texture>,
+        projector: Projector,
+        position: Point,
+        target: Point,
+        up: Vec3,
+    ) -> Self {
+        let w = (target - position).unit();
+        let u = w.cross(&up).unit();
+        let v = u.cross(&w);
+
+        Self {
+            texture,
+            projector,
+            position,
+            u,
+            v,
+            w,
+        }
+    }
+
+    /// The UV coordinates `p` projects to, if it lies within the projected area.
+    pub fn project(&self, p: Point) -> Option<(f32, f32)> {
+        let (u, v) = self.map(p)?;
+        if (0.0..=1.0).contains(&u) && (0.0..=1.0).contains(&v) {
+            Some((u, v))
+        } else {
+            None
+        }
+    }
+
+    /// Projects `p` without bounding it to the projected area.
+    fn map(&self, p: Point) -> Option<(f32, f32)> {
+        let d = p - self.position;
+        let (x, y, z) = (d.dot(&self.u), d.dot(&self.v), d.dot(&self.w));
+
+        match self.projector {
+            Projector::Planar { width, height } => Some((x / width + 0.5, y / height + 0.5)),
+            // Rotated so the projector's forward direction lands in the center of the image.
+            Projector::Spherical => Some(super::uv_coords(Vec3::new(z, y, -x))),
+            Projector::Perspective { fov, aspect_ratio } => {
+                if z <= 0.0 {
+                    return None;
+                }
+                let h = (fov.to_radians() / 2.0).tan();
+                Some((
+                    x / (z * h * aspect_ratio) * 0.5 + 0.5,
+                    y / (z * h) * 0.5 + 0.5,
+                ))
+            }
+        }
+    }
+
+    /// Carries a surface footprint over into the UV space of the projector.
+    fn project_footprint(&self, p: Point, (u, v): (f32, f32), footprint: &Footprint) -> Footprint {
+        let derivative = |dp: Vec3| match self.map(p + dp) {
+            Some((du, dv)) => (du - u, dv - v),
+            None => (0.0, 0.0),
+        };
+        let (dudx, dvdx) = derivative(footprint.dpdx);
+        let (dudy, dvdy) = derivative(footprint.dpdy);
+
+        Footprint {
+            dudx,
+            dvdx,
+            dudy,
+            dvdy,
+            ..*footprint
+        }
+    }
+
+    /// The color projected along `direction`, treating the projector as a point light.
+    pub fn color_towards(&self, direction: Vec3) -> Color {
+        let p = self.position + direction.unit();
+        match self.project(p) {
+            Some((u, v)) => self.texture.color(u, v, p),
+            None => Color::new(0.0, 0.0, 0.0),
+        }
+    }
+
+    fn eval(&self, p: Point, texture: &dyn Texture, footprint: Option<&Footprint>) -> Color {
+        match self.project(p) {
+            Some(uv) => {
+                let footprint = footprint.map(|f| self.project_footprint(p, uv, f));
+                lookup(texture, uv.0, uv.1, p, footprint.as_ref())
+            }
+            None => Color::new(0.0, 0.0, 0.0),
+        }
+    }
+}
+
+impl Texture for Projection {
+    fn color(&self, _u: f32, _v: f32, p: Point) -> Color {
+        self.eval(p, &*self.texture, None)
+    }
+
+    fn color_filtered(&self, _u: f32, _v: f32, p: Point, footprint: &Footprint) -> Color {
+        self.eval(p, &*self.texture, Some(footprint))
+    }
+}
+
+/// Stamps a projected texture over a base texture.
+///
+/// The luminance of `alpha`, looked up through the same projection, controls
+/// coverage. Outside the projected area, the base texture shows through.
+pub struct Decal {
+    projection: Projection,
+    alpha: Option<Box<dyn Texture>>,
+    base: Box<dyn Texture>,
+}
+
+impl Decal {
+    pub fn new(
+        projection: Projection,
+        alpha: Option<Box<dyn Texture>>,
+        base: Box<dyn Texture>,
+    ) -> Self {
+        Self {
+            projection,
+            alpha,
+            base,
+        }
+    }
+
+    fn eval(&self, u: f32, v: f32, p: Point, footprint: Option<&Footprint>) -> Color {
+        let base = lookup(&*self.base, u, v, p, footprint);
+        if self.projection.project(p).is_none() {
+            return base;
+        }
+
+        let alpha = match &self.alpha {
+            Some(alpha) => self.projection.eval(p, &**alpha, footprint).luminance(),
+            None => 1.0,
+        }
+        .clamp(0.0, 1.0);
+
+        let decal = self
+            .projection
+            .eval(p, &*self.projection.texture, footprint);
+        base * (1.0 - alpha) + decal * alpha
+    }
+}
+
+impl Texture for Decal {
+    fn color(&self, u: f32, v: f32, p: Point) -> Color {
+        self.eval(u, v, p, None)
+    }
+
+    fn color_filtered(&self, u: f32, v: f32, p: Point, footprint: &Footprint) -> Color {
+        self.eval(u, v, p, Some(footprint))
+    }
+}
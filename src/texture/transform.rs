@@ -0,0 +1,112 @@
+use super::{lookup, Footprint, Texture};
+use crate::{Color, Point, Vec3};
+
+/// Scales, rotates and offsets the UV coordinates passed to a texture.
+///
+/// The transform is applied as scale, then rotation (in degrees, around
+/// the UV origin), then offset.
+pub struct UvTransform {
+    texture: Box<dyn Texture>,
+    scale: (f32, f32),
+    offset: (f32, f32),
+    rotation: f32,
+}
+
+impl UvTransform {
+    pub fn new(
+        texture: Box<dyn Texture>,
+        scale: (f32, f32),
+        offset: (f32, f32),
+        rotation: f32,
+    ) -> Self {
+        Self {
+            texture,
+            scale,
+            offset,
+            rotation,
+        }
+    }
+
+    fn transform(&self, u: f32, v: f32) -> (f32, f32) {
+        let (u, v) = (u * self.scale.0, v * self.scale.1);
+        let (sin, cos) = self.rotation.to_radians().sin_cos();
+        (u * cos - v * sin, u * sin + v * cos)
+    }
+}
+
+impl Texture for UvTransform {
+    fn color(&self, u: f32, v: f32, p: Point) -> Color {
+        let (u, v) = self.transform(u, v);
+        self.texture.color(u + self.offset.0, v + self.offset.1, p)
+    }
+
+    fn color_filtered(&self, u: f32, v: f32, p: Point, footprint: &Footprint) -> Color {
+        let (u, v) = self.transform(u, v);
+        let (dudx, dvdx) = self.transform(footprint.dudx, footprint.dvdx);
+        let (dudy, dvdy) = self.transform(footprint.dudy, footprint.dvdy);
+        let footprint = Footprint {
+            dudx,
+            dvdx,
+            dudy,
+            dvdy,
+            ..*footprint
+        };
+
+        lookup(
+            &*self.texture,
+            u + self.offset.0,
+            v + self.offset.1,
+            p,
+            Some(&footprint),
+        )
+    }
+}
+
+/// Places a texture in world space by scaling, rotating and translating it.
+///
+/// Rotation is given as XYZ Euler angles in degrees. Points are mapped back
+/// into the texture's local space before lookup.
+pub struct WorldTransform {
+    texture: Box<dyn Texture>,
+    translation: Vec3,
+    rotation: Vec3,
+    scale: Vec3,
+}
+
+impl WorldTransform {
+    pub fn new(texture: Box<dyn Texture>, translation: Vec3, rotation: Vec3, scale: Vec3) -> Self {
+        Self {
+            texture,
+            translation,
+            rotation,
+            scale,
+        }
+    }
+
+    fn to_local(&self, p: Point) -> Point {
+        self.to_local_vector(p - self.translation)
+    }
+
+    fn to_local_vector(&self, v: Vec3) -> Vec3 {
+        let v = v.rotate(Vec3::new(0.0, 0.0, 1.0), -self.rotation.z.to_radians());
+        let v = v.rotate(Vec3::new(0.0, 1.0, 0.0), -self.rotation.y.to_radians());
+        let v = v.rotate(Vec3::new(1.0, 0.0, 0.0), -self.rotation.x.to_radians());
+        Vec3::new(v.x / self.scale.x, v.y / self.scale.y, v.z / self.scale.z)
+    }
+}
+
+impl Texture for WorldTransform {
+    fn color(&self, u: f32, v: f32, p: Point) -> Color {
+        self.texture.color(u, v, self.to_local(p))
+    }
+
+    fn color_filtered(&self, u: f32, v: f32, p: Point, footprint: &Footprint) -> Color {
+        let footprint = Footprint {
+            dpdx: self.to_local_vector(footprint.dpdx),
+            dpdy: self.to_local_vector(footprint.dpdy),
+            ..*footprint
+        };
+        self.texture
+            .color_filtered(u, v, self.to_local(p), &footprint)
+    }
+}
@@ -0,0 +1,73 @@
+//! Splitting a render into tiles, and the order they are rendered in.
+
+/// The order in which tiles are handed out to render threads.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TileOrder {
+    /// Row by row, from the top left.
+    #[default]
+    Scanline,
+    /// Outwards from the center, which usually holds the subject.
+    Spiral,
+    /// Along a Hilbert curve, keeping consecutive tiles close together.
+    Hilbert,
+}
+
+type Region = ((usize, usize), (usize, usize));
+
+/// Splits `region` into tiles of at most `size` by `size` pixels, in `order`.
+pub(crate) fn tiles(region: Region, size: usize, order: TileOrder) -> Vec<Region> {
+    let ((x_start, x_end), (y_start, y_end)) = region;
+    let columns = (x_end - x_start).div_ceil(size);
+    let rows = (y_end - y_start).div_ceil(size);
+
+    let mut cells: Vec<(usize, usize)> = (0..rows)
+        .flat_map(|row| (0..columns).map(move |column| (column, row)))
+        .collect();
+
+    match order {
+        TileOrder::Scanline => {}
+        TileOrder::Spiral => {
+            let center = ((columns as f32 - 1.0) / 2.0, (rows as f32 - 1.0) / 2.0);
+            let key = |&(column, row): &(usize, usize)| {
+                let (dx, dy) = (column as f32 - center.0, row as f32 - center.1);
+                let ring = dx.abs().max(dy.abs());
+                (ring, dy.atan2(dx))
+            };
+            cells.sort_by(|a, b| key(a).partial_cmp(&key(b)).unwrap());
+        }
+        TileOrder::Hilbert => {
+            let n = columns.max(rows).next_power_of_two();
+            cells.sort_by_key(|&(column, row)| hilbert_index(n, column, row));
+        }
+    }
+
+    cells
+        .into_iter()
+        .map(|(column, row)| {
+            let (x, y) = (x_start + column * size, y_start + row * size);
+            ((x, (x + size).min(x_end)), (y, (y + size).min(y_end)))
+        })
+        .collect()
+}
+
+/// Distance along a Hilbert curve filling an `n` by `n` grid, where `n` is a power of two.
+fn hilbert_index(n: usize, mut x: usize, mut y: usize) -> usize {
+    let mut index = 0;
+    let mut s = n / 2;
+    while s > 0 {
+        let rx = (x & s > 0) as usize;
+        let ry = (y & s > 0) as usize;
+        index += s * s * ((3 * rx) ^ ry);
+
+        // Rotate the quadrant so the curve inside it has the right orientation.
+        if ry == 0 {
+            if rx == 1 {
+                x = s - 1 - (x & (s - 1));
+                y = s - 1 - (y & (s - 1));
+            }
+            std::mem::swap(&mut x, &mut y);
+        }
+        s /= 2;
+    }
+    index
+}
@@ -0,0 +1,229 @@
+//! Conversion of linear render results into displayable colors.
+
+use super::Color;
+
+type Matrix = [[f32; 3]; 3];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Tonemapper {
+    /// Clips every channel to `[0, 1]`.
+    Clamp,
+    /// Reinhard's operator applied to luminance, `L / (1 + L)`.
+    Reinhard,
+    /// Reinhard's operator with luminance `white` mapped to 1.
+    ExtendedReinhard { white: f32 },
+    /// Stephen Hill's fit of the ACES filmic reference rendering transform.
+    Aces,
+    /// An approximation of the AgX view transform, which desaturates bright colors gracefully.
+    Agx,
+}
+
+/// Settings for turning a linear image into a displayable one.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OutputSettings {
+    /// Exposure adjustment in stops.
+    pub exposure: f32,
+    /// Color temperature in kelvin of the light that should appear white,
+    /// or `None` to leave colors unchanged.
+    pub white_balance: Option<f32>,
+    pub tonemapper: Tonemapper,
+}
+
+impl Default for OutputSettings {
+    fn default() -> Self {
+        Self {
+            exposure: 0.0,
+            white_balance: None,
+            tonemapper: Tonemapper::Clamp,
+        }
+    }
+}
+
+impl OutputSettings {
+    /// Applies exposure, white balance and the tonemapper, returning linear
+    /// colors in `[0, 1]` that are ready for sRGB encoding.
+    pub fn apply(&self, color: Color) -> Color {
+        let mut color = color * 2f32.powf(self.exposure);
+
+        if let Some(temperature) = self.white_balance {
+            color = mul(&white_balance(temperature), color);
+        }
+
+        let color = match self.tonemapper {
+            Tonemapper::Clamp => color,
+            Tonemapper::Reinhard => scale_luminance(color, |l| l / (1.0 + l)),
+            Tonemapper::ExtendedReinhard { white } => {
+                scale_luminance(color, |l| l * (1.0 + l / (white * white)) / (1.0 + l))
+            }
+            Tonemapper::Aces => aces(color),
+            Tonemapper::Agx => agx(color),
+        };
+
+        Color::new(
+            color.x.clamp(0.0, 1.0),
+            color.y.clamp(0.0, 1.0),
+            color.z.clamp(0.0, 1.0),
+        )
+    }
+}
+
+/// The sRGB transfer function, mapping linear values in `[0, 1]` to encoded ones.
+pub fn linear_to_srgb(c: f32) -> f32 {
+    if c <= 0.003_130_8 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// The inverse of `linear_to_srgb`.
+pub fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn scale_luminance(color: Color, f: impl Fn(f32) -> f32) -> Color {
+    let luminance = color.luminance();
+    if luminance <= 0.0 {
+        return Color::new(0.0, 0.0, 0.0);
+    }
+    color * (f(luminance) / luminance)
+}
+
+fn aces(color: Color) -> Color {
+    const INPUT: Matrix = [
+        [0.59719, 0.35458, 0.04823],
+        [0.07600, 0.90834, 0.01566],
+        [0.02840, 0.13383, 0.83777],
+    ];
+    const OUTPUT: Matrix = [
+        [1.60475, -0.53108, -0.07367],
+        [-0.10208, 1.10813, -0.00605],
+        [-0.00327, -0.07276, 1.07602],
+    ];
+
+    let fit = |v: f32| {
+        let a = v * (v + 0.024_578_6) - 0.000_090_537;
+        let b = v * (0.983_729 * v + 0.432_951) + 0.238_081;
+        a / b
+    };
+
+    let c = mul(&INPUT, color);
+    mul(&OUTPUT, Color::new(fit(c.x), fit(c.y), fit(c.z)))
+}
+
+fn agx(color: Color) -> Color {
+    const INSET: Matrix = [
+        [0.842_479_1, 0.078_433_6, 0.079_223_75],
+        [0.042_328_24, 0.878_468_6, 0.079_166_13],
+        [0.042_375_65, 0.078_433_6, 0.879_143],
+    ];
+    const OUTSET: Matrix = [
+        [1.196_879, -0.098_020_88, -0.099_029_74],
+        [-0.052_896_85, 1.151_903_1, -0.098_961_18],
+        [-0.052_971_64, -0.098_043_45, 1.151_073_7],
+    ];
+    const MIN_EV: f32 = -12.473_93;
+    const MAX_EV: f32 = 4.026_069;
+
+    let curve = |v: f32| {
+        let v = (v.max(1e-10).log2().clamp(MIN_EV, MAX_EV) - MIN_EV) / (MAX_EV - MIN_EV);
+        let (v2, v4) = (v * v, v * v * v * v);
+        15.5 * v4 * v2 - 40.14 * v4 * v + 31.96 * v4 - 6.868 * v2 * v + 0.4298 * v2 + 0.1191 * v
+            - 0.00232
+    };
+
+    let c = mul(&INSET, color);
+    let c = mul(&OUTSET, Color::new(curve(c.x), curve(c.y), curve(c.z)));
+
+    // The curve produces display encoded values; undo the display gamma to stay linear.
+    let linear = |v: f32| v.max(0.0).powf(2.2);
+    Color::new(linear(c.x), linear(c.y), linear(c.z))
+}
+
+/// A chromatic adaptation from the white point of a blackbody at `temperature` to D65.
+fn white_balance(temperature: f32) -> Matrix {
+    const SRGB_TO_XYZ: Matrix = [
+        [0.412_456_4, 0.357_576_1, 0.180_437_5],
+        [0.212_672_9, 0.715_152_2, 0.072_175],
+        [0.019_333_9, 0.119_192, 0.950_304_1],
+    ];
+    const XYZ_TO_SRGB: Matrix = [
+        [3.240_454_2, -1.537_138_5, -0.498_531_4],
+        [-0.969_266, 1.876_010_8, 0.041_556],
+        [0.055_643_4, -0.204_025_9, 1.057_225_2],
+    ];
+    const BRADFORD: Matrix = [
+        [0.8951, 0.2664, -0.1614],
+        [-0.7502, 1.7135, 0.0367],
+        [0.0389, -0.0685, 1.0296],
+    ];
+    const BRADFORD_INVERSE: Matrix = [
+        [0.986_992_9, -0.147_054_3, 0.159_962_7],
+        [0.432_305_3, 0.518_360_3, 0.049_291_2],
+        [-0.008_528_7, 0.040_042_8, 0.968_486_7],
+    ];
+
+    let (x, y) = white_point(temperature);
+    let source = mul(&BRADFORD, Color::new(x / y, 1.0, (1.0 - x - y) / y));
+    let target = mul(&BRADFORD, Color::new(0.950_47, 1.0, 1.088_83));
+
+    let scale = [
+        [target.x / source.x, 0.0, 0.0],
+        [0.0, target.y / source.y, 0.0],
+        [0.0, 0.0, target.z / source.z],
+    ];
+
+    let adapt = matmul(&BRADFORD_INVERSE, &matmul(&scale, &BRADFORD));
+    matmul(&XYZ_TO_SRGB, &matmul(&adapt, &SRGB_TO_XYZ))
+}
+
+/// Chromaticity of a white at `temperature`.
+///
+/// Below 4000 K this follows the blackbody locus, using the approximation by
+/// Kang et al. (2002); above it, the CIE daylight locus, so 6504 K is D65.
+fn white_point(temperature: f32) -> (f32, f32) {
+    let t = temperature.clamp(1667.0, 25000.0);
+    let (t2, t3) = (t * t, t * t * t);
+
+    if t >= 4000.0 {
+        let x = if t <= 7000.0 {
+            -4.6070e9 / t3 + 2.9678e6 / t2 + 0.09911e3 / t + 0.244_063
+        } else {
+            -2.0064e9 / t3 + 1.9018e6 / t2 + 0.24748e3 / t + 0.237_040
+        };
+        return (x, -3.0 * x * x + 2.870 * x - 0.275);
+    }
+
+    let x = -0.266_123_9e9 / t3 - 0.234_358_9e6 / t2 + 0.877_695_6e3 / t + 0.179_910;
+
+    let (x2, x3) = (x * x, x * x * x);
+    let y = if t <= 2222.0 {
+        -1.106_381_4 * x3 - 1.348_110_2 * x2 + 2.185_558_3 * x - 0.202_196_83
+    } else {
+        -0.954_947_6 * x3 - 1.374_185_9 * x2 + 2.091_37 * x - 0.167_488_67
+    };
+
+    (x, y)
+}
+
+fn mul(m: &Matrix, c: Color) -> Color {
+    Color::new(
+        m[0][0] * c.x + m[0][1] * c.y + m[0][2] * c.z,
+        m[1][0] * c.x + m[1][1] * c.y + m[1][2] * c.z,
+        m[2][0] * c.x + m[2][1] * c.y + m[2][2] * c.z,
+    )
+}
+
+fn matmul(a: &Matrix, b: &Matrix) -> Matrix {
+    let mut m = [[0.0; 3]; 3];
+    for (i, row) in m.iter_mut().enumerate() {
+        for (j, value) in row.iter_mut().enumerate() {
+            *value = (0..3).map(|k| a[i][k] * b[k][j]).sum();
+        }
+    }
+    m
+}
@@ -1,7 +1,8 @@
-use super::random;
+use super::Sampler;
 use core::ops::{Add, Div, Mul, Neg, Sub};
+use std::f32::consts::PI;
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Default)]
 pub struct Vec3 {
     pub x: f32,
     pub y: f32,
@@ -48,27 +49,79 @@ impl Vec3 {
         r_perp + r_para
     }
 
+    /// Rotates the vector around `axis` by `angle` radians (Rodrigues' formula).
+    pub fn rotate(&self, axis: Vec3, angle: f32) -> Vec3 {
+        let k = axis.unit();
+        let (sin, cos) = angle.sin_cos();
+        *self * cos + k.cross(self) * sin + k * (k.dot(self) * (1.0 - cos))
+    }
+
+    /// Returns two unit vectors that form an orthonormal basis with this (unit) vector.
+    pub fn orthonormal_basis(&self) -> (Vec3, Vec3) {
+        let sign = 1f32.copysign(self.z);
+        let a = -1.0 / (sign + self.z);
+        let b = self.x * self.y * a;
+        (
+            Vec3::new(1.0 + sign * self.x * self.x * a, sign * b, -sign * self.x),
+            Vec3::new(b, sign + self.y * self.y * a, -self.y),
+        )
+    }
+
+    /// Relative luminance of the vector interpreted as a linear Rec. 709 color.
+    #[inline(always)]
+    pub fn luminance(&self) -> f32 {
+        0.2126 * self.x + 0.7152 * self.y + 0.0722 * self.z
+    }
+
+    pub fn abs(&self) -> Vec3 {
+        Vec3::new(self.x.abs(), self.y.abs(), self.z.abs())
+    }
+
+    /// The coordinate along axis 0, 1 or 2.
+    pub fn axis(&self, axis: usize) -> f32 {
+        match axis {
+            0 => self.x,
+            1 => self.y,
+            _ => self.z,
+        }
+    }
+
     pub fn near_zero(&self) -> bool {
         let epsilon = 1e-7;
         (self.x.abs() < epsilon) && (self.y.abs() < epsilon) && (self.z.abs() < epsilon)
     }
 
-    pub fn random_in_unit_sphere() -> Vec3 {
-        loop {
-            let v = Vec3::new(random(-1.0, 1.0), random(-1.0, 1.0), random(-1.0, 1.0));
-            if v.len() < 1.0 {
-                return v;
-            }
-        }
+    /// A uniformly distributed point inside the unit sphere.
+    pub fn random_in_unit_sphere(sampler: &mut dyn Sampler) -> Vec3 {
+        let radius = sampler.next_1d().cbrt();
+        Vec3::random_unit_vector(sampler) * radius
     }
 
-    pub fn random_in_unit_disk() -> Vec3 {
-        loop {
-            let v = Vec3::new(random(-1.0, 1.0), random(-1.0, 1.0), 0.0);
-            if v.len() < 1.0 {
-                return v;
-            }
+    /// A uniformly distributed point on the unit sphere.
+    pub fn random_unit_vector(sampler: &mut dyn Sampler) -> Vec3 {
+        let (u, v) = sampler.next_2d();
+        let z = 1.0 - 2.0 * u;
+        let r = (1.0 - z * z).max(0.0).sqrt();
+        let phi = 2.0 * PI * v;
+        Vec3::new(r * phi.cos(), r * phi.sin(), z)
+    }
+
+    /// A uniformly distributed point inside the unit disk in the XY plane,
+    /// using Shirley and Chiu's concentric mapping, which keeps samples
+    /// evenly spread.
+    pub fn random_in_unit_disk(sampler: &mut dyn Sampler) -> Vec3 {
+        let (u, v) = sampler.next_2d();
+        let (a, b) = (2.0 * u - 1.0, 2.0 * v - 1.0);
+        if a == 0.0 && b == 0.0 {
+            return Vec3::new(0.0, 0.0, 0.0);
         }
+
+        let (r, theta) = if a.abs() > b.abs() {
+            (a, PI / 4.0 * (b / a))
+        } else {
+            (b, PI / 2.0 - PI / 4.0 * (a / b))
+        };
+        Vec3::new(r * theta.cos(), r * theta.sin(), 0.0)
     }
 }
 
@@ -104,6 +157,14 @@ impl Mul<f32> for Vec3 {
     }
 }
 
+impl Mul<Vec3> for Vec3 {
+    type Output = Vec3;
+
+    fn mul(self, other: Vec3) -> Vec3 {
+        Vec3::new(self.x * other.x, self.y * other.y, self.z * other.z)
+    }
+}
+
 impl Mul<Vec3> for f32 {
     type Output = Vec3;
 
@@ -119,3 +180,11 @@ impl Div<f32> for Vec3 {
         Vec3::new(self.x / other, self.y / other, self.z / other)
     }
 }
+
+impl Div<Vec3> for Vec3 {
+    type Output = Vec3;
+
+    fn div(self, other: Vec3) -> Vec3 {
+        Vec3::new(self.x / other.x, self.y / other.y, self.z / other.z)
+    }
+}
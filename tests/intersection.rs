@@ -0,0 +1,207 @@
+use ocular::object::Sphere;
+use ocular::texture::Solid;
+use ocular::*;
+
+fn material() -> Box<dyn Material> {
+    Box::new(material::Diffuse::new(Box::new(Solid::new(Color::new(
+        0.5, 0.5, 0.5,
+    )))))
+}
+
+fn random_unit_vector(rng: &mut Rng) -> Vec3 {
+    loop {
+        let v = Vec3::new(
+            rng.range(-1.0, 1.0),
+            rng.range(-1.0, 1.0),
+            rng.range(-1.0, 1.0),
+        );
+        let len = v.len();
+        if len > 1e-3 && len <= 1.0 {
+            return v / len;
+        }
+    }
+}
+
+/// A flat grid of uneven quads, split into triangles along alternating
+/// diagonals, in a tilted plane so that no coordinate is exact.
+fn grid(size: usize, rng: &mut Rng) -> (Vec<Point>, Vec<[u32; 3]>) {
+    let mut vertices = Vec::new();
+    for j in 0..=size {
+        for i in 0..=size {
+            let x = i as f32 + rng.range(-0.3, 0.3);
+            let y = j as f32 + rng.range(-0.3, 0.3);
+            vertices.push(Point::new(x, y, 0.3 * x - 0.2 * y));
+        }
+    }
+
+    let mut faces = Vec::new();
+    let index = |i: usize, j: usize| (j * (size + 1) + i) as u32;
+    for j in 0..size {
+        for i in 0..size {
+            let (a, b, c, d) = (
+                index(i, j),
+                index(i + 1, j),
+                index(i + 1, j + 1),
+                index(i, j + 1),
+            );
+            if (i + j) % 2 == 0 {
+                faces.extend([[a, b, c], [a, c, d]]);
+            } else {
+                faces.extend([[a, b, d], [b, c, d]]);
+            }
+        }
+    }
+    (vertices, faces)
+}
+
+#[test]
+fn rays_through_shared_edges_and_vertices_hit_the_mesh_once() {
+    let mut rng = Rng::new(1, 0);
+    let (vertices, faces) = grid(4, &mut rng);
+    let triangles: Vec<Trig> = faces
+        .iter()
+        .map(|f| {
+            Trig::new(
+                vertices[f[0] as usize],
+                vertices[f[1] as usize],
+                vertices[f[2] as usize],
+            )
+        })
+        .collect();
+    let mesh = Mesh::new(
+        vertices.clone(),
+        faces.clone(),
+        Point::new(0.0, 0.0, 0.0),
+        material(),
+    );
+
+    // Points on every edge, which rounding leaves just either side of it,
+    // and every vertex.
+    let mut targets = Vec::new();
+    for face in &faces {
+        for k in 0..3 {
+            let (a, b) = (
+                vertices[face[k] as usize],
+                vertices[face[(k + 1) % 3] as usize],
+            );
+            for s in [0.1, 0.25, 1.0 / 3.0, 0.5, 0.7, 0.9] {
+                targets.push(a + (b - a) * s);
+            }
+        }
+    }
+    targets.extend(vertices.iter().copied());
+
+    for target in targets {
+        for _ in 0..8 {
+            let inside = |p: Point| (0.3..3.7).contains(&p.x) && (0.3..3.7).contains(&p.y);
+            if !inside(target) {
+                continue;
+            }
+            let direction = random_unit_vector(&mut rng);
+            let ray = Ray::new(target - direction * 3.0, direction);
+
+            let hits = triangles.iter().filter(|t| t.hit(ray).is_some()).count();
+            assert!(
+                hits >= 1,
+                "ray towards {target:?} slipped between triangles"
+            );
+
+            let record = mesh
+                .hit(ray, 0.0, f32::INFINITY)
+                .expect("mesh should be hit");
+            let onward = record.spawn_ray(direction);
+            assert!(
+                mesh.hit(onward, 0.0, f32::INFINITY).is_none(),
+                "ray towards {target:?} hit the mesh twice"
+            );
+        }
+    }
+}
+
+#[test]
+fn spawned_rays_leave_spheres_at_any_scale() {
+    for scale in [1e-4, 1.0, 1e4] {
+        let mut rng = Rng::new(2, 0);
+        let center = Point::new(30.0, -20.0, 50.0) * scale;
+        let sphere = Sphere::new(center, scale, material());
+
+        for _ in 0..2000 {
+            let origin = center + random_unit_vector(&mut rng) * (3.0 * scale);
+            let target = center + random_unit_vector(&mut rng) * (0.99 * scale);
+            let ray = Ray::new(origin, target - origin);
+            let Some(record) = sphere.hit(ray, 0.0, f32::INFINITY) else {
+                continue;
+            };
+
+            // Rays leaving the outside can't hit the sphere at all.
+            let mut outwards = random_unit_vector(&mut rng);
+            if outwards.dot(&record.normal) < 0.0 {
+                outwards = -outwards;
+            }
+            for direction in [ray.direction.reflect(record.normal), outwards] {
+                let hit = sphere.hit(record.spawn_ray(direction), 0.0, f32::INFINITY);
+                assert!(
+                    hit.is_none(),
+                    "scale {scale}: spawned ray hit its own surface"
+                );
+            }
+
+            // Rays into the sphere reach the far side, not their own point.
+            let mut inwards = random_unit_vector(&mut rng);
+            if inwards.dot(&record.normal) > 0.0 {
+                inwards = -inwards;
+            }
+            if inwards.dot(&record.normal) > -0.1 {
+                continue;
+            }
+            let spawned = record.spawn_ray(inwards);
+            let far = sphere
+                .hit(spawned, 0.0, f32::INFINITY)
+                .expect("ray into the sphere should leave it");
+            let distance = far.t * spawned.direction.len();
+            assert!(
+                distance > 0.1 * scale,
+                "scale {scale}: ray into the sphere hit its own point"
+            );
+        }
+    }
+}
+
+#[test]
+fn spawned_rays_leave_triangles_at_any_scale() {
+    for scale in [1e-4, 1.0, 1e4] {
+        let mut rng = Rng::new(3, 0);
+        let offset = Point::new(30.0, -20.0, 50.0) * scale;
+        // A tilted square, so that no coordinate of its points is exact.
+        let (u, v) = (Vec3::new(1.0, 0.1, -0.2), Vec3::new(0.2, 1.0, 0.3));
+        let vertices = vec![
+            Point::new(0.0, 0.0, 0.0),
+            u * scale,
+            (u + v) * scale,
+            v * scale,
+        ];
+        let mesh = Mesh::new(vertices, vec![[0, 1, 2], [0, 2, 3]], offset, material());
+
+        for _ in 0..2000 {
+            let target = offset + (u * rng.range(0.05, 0.95) + v * rng.range(0.05, 0.95)) * scale;
+            let origin = target + random_unit_vector(&mut rng) * (3.0 * scale);
+            let ray = Ray::new(origin, target - origin);
+            let Some(record) = mesh.hit(ray, 0.0, f32::INFINITY) else {
+                continue;
+            };
+
+            // The square is flat, so rays leaving it on either side never hit it again.
+            let mut random = random_unit_vector(&mut rng);
+            if random.dot(&record.normal).abs() < 1e-3 {
+                random = record.normal;
+            }
+            for direction in [ray.direction.reflect(record.normal), ray.direction, random] {
+                let hit = mesh.hit(record.spawn_ray(direction), 0.0, f32::INFINITY);
+                assert!(
+                    hit.is_none(),
+                    "scale {scale}: spawned ray hit its own surface"
+                );
+            }
+        }
+    }
+}
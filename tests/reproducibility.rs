@@ -0,0 +1,200 @@
+use ocular::object::Sphere;
+use ocular::texture::Solid;
+use ocular::*;
+
+fn scene() -> Scene {
+    let camera = Camera::new(
+        Point::new(0.0, 1.0, 4.0),
+        Point::new(0.0, 0.5, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
+        40.0,
+        1.0,
+        0.05,
+        4.0,
+    );
+    let solid = |r, g, b| Box::new(Solid::new(Color::new(r, g, b)));
+    let objects: Vec<Box<dyn Hittable>> = vec![
+        Box::new(Sphere::new(
+            Point::new(0.0, -100.0, 0.0),
+            100.0,
+            Box::new(material::Diffuse::new(solid(0.7, 0.7, 0.7))),
+        )),
+        Box::new(Sphere::new(
+            Point::new(-0.6, 0.5, 0.0),
+            0.5,
+            Box::new(material::Metal::new(solid(0.9, 0.8, 0.7), 0.2)),
+        )),
+        Box::new(Sphere::new(
+            Point::new(0.6, 0.5, 0.0),
+            0.5,
+            Box::new(material::Glass::new(1.5)),
+        )),
+        Box::new(Sphere::new(
+            Point::new(0.0, 2.0, 0.5),
+            0.3,
+            Box::new(material::Light::new(solid(8.0, 8.0, 8.0))),
+        )),
+    ];
+    Scene::new(camera, solid(0.2, 0.3, 0.5), objects)
+}
+
+const SIZE: usize = 24;
+
+fn options(
+    integrator: IntegratorKind,
+    filter: Filter,
+    threads: usize,
+    block_size: usize,
+) -> RenderOptions {
+    RenderOptions {
+        width: SIZE,
+        height: SIZE,
+        crop_region: ((0, SIZE), (0, SIZE)),
+        samples: 4,
+        bounces: 3,
+        seed: 7,
+        sampler: SamplerKind::Sobol,
+        integrator,
+        clip_start: 0.001,
+        clip_end: f32::INFINITY,
+        block_size,
+        threads: Some(threads),
+        tile_order: TileOrder::Spiral,
+        passes: Passes::NONE,
+        denoise: None,
+        adaptive: None,
+        filter,
+        guiding: None,
+        clamp: None,
+        accumulation: Accumulation::Mean,
+    }
+}
+
+/// The bits of each channel of `image`, so that images can be compared exactly.
+fn pixels(image: &HdrImage) -> Vec<[u32; 3]> {
+    let mut pixels = Vec::new();
+    for y in 0..SIZE {
+        for x in 0..SIZE {
+            let color = image.get_pixel(x, y);
+            pixels.push([color.x.to_bits(), color.y.to_bits(), color.z.to_bits()]);
+        }
+    }
+    pixels
+}
+
+fn render(
+    scene: &Scene,
+    integrator: IntegratorKind,
+    filter: Filter,
+    threads: usize,
+    block_size: usize,
+) -> Vec<[u32; 3]> {
+    let options = options(integrator, filter, threads, block_size);
+    pixels(&scene.threaded_render(options).beauty)
+}
+
+#[test]
+fn same_image_whatever_the_thread_count() {
+    let scene = scene();
+    let cases = [
+        (
+            IntegratorKind::PathTracer,
+            Filter::Mitchell {
+                radius: 2.0,
+                b: 1.0 / 3.0,
+                c: 1.0 / 3.0,
+            },
+        ),
+        (
+            IntegratorKind::Bidirectional,
+            Filter::Gaussian {
+                radius: 1.5,
+                sigma: 0.5,
+            },
+        ),
+    ];
+
+    for (integrator, filter) in cases {
+        for block_size in [5, 8] {
+            let single = render(&scene, integrator, filter, 1, block_size);
+            let threaded = render(&scene, integrator, filter, 4, block_size);
+            assert!(
+                single == threaded,
+                "{integrator:?} with blocks of {block_size} differs between 1 and 4 threads"
+            );
+        }
+    }
+}
+
+#[test]
+fn same_image_whatever_the_block_size() {
+    let scene = scene();
+    let cases = [
+        // Each sample only reaches its own pixel, so pixels are summed in the
+        // same order however the image is split.
+        (IntegratorKind::PathTracer, Filter::default(), 0.0),
+        // Wider filters and splats reach across blocks, so a different split
+        // sums some pixels in a different order, which may change their last bit.
+        (
+            IntegratorKind::PathTracer,
+            Filter::Lanczos { radius: 3.0 },
+            1e-6,
+        ),
+        (
+            IntegratorKind::Bidirectional,
+            Filter::Gaussian {
+                radius: 1.5,
+                sigma: 0.5,
+            },
+            1e-6,
+        ),
+    ];
+
+    for (integrator, filter, tolerance) in cases {
+        let reference = render(&scene, integrator, filter, 1, 5);
+        for (threads, block_size) in [(4, 5), (1, 8), (4, 7), (2, 16)] {
+            let image = render(&scene, integrator, filter, threads, block_size);
+            let difference = max_difference(&image, &reference);
+            assert!(
+                difference <= tolerance,
+                "{integrator:?} with {threads} threads and blocks of {block_size} \
+                 differs by {difference}"
+            );
+        }
+    }
+}
+
+/// The largest difference between the channels of two images, relative to
+/// the brighter of the two.
+fn max_difference(a: &[[u32; 3]], b: &[[u32; 3]]) -> f32 {
+    let mut max = 0.0f32;
+    for (a, b) in a.iter().flatten().zip(b.iter().flatten()) {
+        let (a, b) = (f32::from_bits(*a), f32::from_bits(*b));
+        if a != b {
+            max = max.max((a - b).abs() / a.abs().max(b.abs()));
+        }
+    }
+    max
+}
+
+#[test]
+fn same_guided_image_whatever_the_thread_count() {
+    // Path guiding learns from each pass, so what it learns mustn't depend on
+    // timing either.
+    let scene = scene();
+    let render = |threads| {
+        let options = RenderOptions {
+            samples: 8,
+            guiding: Some(PathGuiding {
+                spatial_threshold: 200,
+                ..PathGuiding::default()
+            }),
+            ..options(IntegratorKind::PathTracer, Filter::default(), threads, 5)
+        };
+        pixels(&scene.progressive_render(options, 2, |_| true).beauty)
+    };
+    assert!(
+        render(1) == render(4),
+        "guided renders differ between 1 and 4 threads"
+    );
+}